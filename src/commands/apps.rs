@@ -0,0 +1,388 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use crate::util::api::{AppStatus, Deployment, DeploymentApi, HttpClient, LogEntry};
+use crate::util::{environment, project};
+
+/// Delay between polls when following logs
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Apps to act on: the one given, or every app in klave.json
+fn app_slugs(app: Option<String>) -> Result<Vec<String>> {
+    if let Some(app) = app {
+        return Ok(vec![app]);
+    }
+
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let klave_config = environment::resolve(&cwd, None)
+        .context("Pass an app slug or run the command in a Klave project")?;
+    let applications = project::applications(&klave_config)?;
+
+    Ok(project::select_apps(applications, None)?
+        .into_iter()
+        .map(|app| project::app_slug(app).to_string())
+        .collect())
+}
+
+/// The app given, or the only app in klave.json
+fn single_app(app: Option<String>) -> Result<String> {
+    let mut slugs = app_slugs(app)?;
+    if slugs.len() > 1 {
+        return Err(anyhow!(
+            "The project has several applications, choose one of: {}",
+            slugs.join(", ")
+        ));
+    }
+    Ok(slugs.remove(0))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn status_color(status: &str) -> ColoredString {
+    match status {
+        "deployed" | "running" | "active" => status.green(),
+        "failed" | "error" | "stopped" => status.red(),
+        _ => status.yellow(),
+    }
+}
+
+fn print_deployment(deployment: &Deployment) {
+    println!(
+        "  {}  {}  {}  {}  {}",
+        deployment.id.bold(),
+        status_color(&deployment.status),
+        deployment.version,
+        deployment
+            .artifact
+            .get(..12)
+            .unwrap_or(&deployment.artifact)
+            .dimmed(),
+        deployment.created_at.as_deref().unwrap_or("").dimmed()
+    );
+}
+
+/// Show the state of applications
+pub fn status(app: Option<String>, api_url: Option<String>, json: bool) -> Result<()> {
+    let client = HttpClient::configured(api_url.as_deref())?;
+    show_status(&client, &app_slugs(app)?, json).map(|_| ())
+}
+
+fn show_status(client: &dyn DeploymentApi, slugs: &[String], json: bool) -> Result<Vec<AppStatus>> {
+    let statuses = slugs
+        .iter()
+        .map(|slug| client.app_status(slug))
+        .collect::<Result<Vec<_>>>()?;
+
+    if json {
+        print_json(&statuses)?;
+        return Ok(statuses);
+    }
+
+    for status in &statuses {
+        println!("{} {}", status.app.bold(), status_color(&status.status));
+        if let Some(url) = &status.url {
+            println!("  {}", url.cyan());
+        }
+        match &status.deployment {
+            Some(deployment) => print_deployment(deployment),
+            None => println!("  {}", "Not deployed".dimmed()),
+        }
+    }
+    Ok(statuses)
+}
+
+/// List the deployments of applications
+pub fn list_deployments(app: Option<String>, api_url: Option<String>, json: bool) -> Result<()> {
+    let client = HttpClient::configured(api_url.as_deref())?;
+    show_deployments(&client, &app_slugs(app)?, json).map(|_| ())
+}
+
+fn show_deployments(
+    client: &dyn DeploymentApi,
+    slugs: &[String],
+    json: bool,
+) -> Result<Vec<Deployment>> {
+    let mut all = Vec::new();
+    for slug in slugs {
+        let deployments = client.list_deployments(slug)?;
+        if !json {
+            println!("{} ({} deployments)", slug.bold(), deployments.len());
+            for deployment in &deployments {
+                print_deployment(deployment);
+            }
+        }
+        all.extend(deployments);
+    }
+
+    if json {
+        print_json(&all)?;
+    }
+    Ok(all)
+}
+
+/// Print the logs of an application, optionally waiting for new entries
+pub fn logs(app: Option<String>, api_url: Option<String>, follow: bool, json: bool) -> Result<()> {
+    let client = HttpClient::configured(api_url.as_deref())?;
+    let slug = single_app(app)?;
+    let mut position = LogPosition::default();
+
+    loop {
+        show_logs(&client, &slug, &mut position, json)?;
+        if !follow {
+            return Ok(());
+        }
+        thread::sleep(LOG_POLL_INTERVAL);
+    }
+}
+
+/// How far the logs of an application have been printed
+#[derive(Default)]
+struct LogPosition {
+    /// Cursor returned by the API, to fetch the following entries
+    cursor: Option<String>,
+    /// Timestamp of the most recent entry printed
+    timestamp: Option<String>,
+    /// Entries printed with that timestamp, as JSON
+    printed: HashSet<String>,
+}
+
+impl LogPosition {
+    /// Whether an entry comes after the ones printed so far
+    ///
+    /// Timestamps are RFC 3339 in UTC, so they compare as strings.
+    fn is_new(&self, entry: &LogEntry, key: &str) -> bool {
+        match &self.timestamp {
+            None => true,
+            Some(timestamp) => {
+                entry.timestamp > *timestamp
+                    || (entry.timestamp == *timestamp && !self.printed.contains(key))
+            }
+        }
+    }
+
+    fn record(&mut self, entry: &LogEntry, key: String) {
+        if self.timestamp.as_ref() != Some(&entry.timestamp) {
+            self.timestamp = Some(entry.timestamp.clone());
+            self.printed.clear();
+        }
+        self.printed.insert(key);
+    }
+}
+
+/// Print the logs after `position`, returning the number of entries printed
+///
+/// When the API does not return a cursor, each request returns entries
+/// already printed, which are skipped.
+fn show_logs(
+    client: &dyn DeploymentApi,
+    slug: &str,
+    position: &mut LogPosition,
+    json: bool,
+) -> Result<usize> {
+    let page = client.logs(slug, position.cursor.as_deref())?;
+    let mut printed = 0;
+    for entry in &page.entries {
+        let key = serde_json::to_string(entry)?;
+        if position.cursor.is_none() && !position.is_new(entry, &key) {
+            continue;
+        }
+        position.record(entry, key.clone());
+        printed += 1;
+
+        if json {
+            println!("{}", key);
+            println!("{}", serde_json::to_string(entry)?);
+            continue;
+        }
+
+        let level = match entry.level.as_str() {
+            "error" => entry.level.red(),
+            "warn" | "warning" => entry.level.yellow(),
+            _ => entry.level.dimmed(),
+        };
+        println!(
+            "{} {:>5} {}",
+            entry.timestamp.dimmed(),
+            level,
+            entry.message
+        );
+    }
+
+    if page.cursor.is_some() {
+        position.cursor = page.cursor;
+    }
+    Ok(printed)
+}
+
+/// Serve a previous deployment of an application again
+pub fn rollback(
+    app: String,
+    deployment: String,
+    api_url: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let client = HttpClient::configured(api_url.as_deref())?;
+    rollback_app(&client, &app, &deployment, json).map(|_| ())
+}
+
+fn rollback_app(
+    client: &dyn DeploymentApi,
+    app: &str,
+    deployment: &str,
+    json: bool,
+) -> Result<Deployment> {
+    let deployment = client.rollback(app, deployment)?;
+
+    if json {
+        print_json(&deployment)?;
+        return Ok(deployment);
+    }
+
+    println!(
+        "{} \"{}\" to deployment {}",
+        "Rolled back".green(),
+        app,
+        deployment.id.bold()
+    );
+    print_deployment(&deployment);
+    Ok(deployment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::api::DeploymentRequest;
+    use crate::util::api::mock::MockApi;
+    use crate::util::api::server::serve;
+    use std::io::Cursor;
+
+    /// Stand-in API with `count` deployments of "hello", the last one serving
+    fn deployed(count: usize) -> MockApi {
+        let api = MockApi::default();
+        for n in 0..count {
+            let artifact = format!("artifact {}", n).into_bytes();
+            let sha256 = crate::util::manifest::sha256(&artifact);
+            api.upload_artifact(
+                "hello",
+                &sha256,
+                &mut Cursor::new(&artifact),
+                artifact.len() as u64,
+            )
+            .unwrap();
+            api.create_deployment(
+                "hello",
+                &DeploymentRequest {
+                    version: format!("0.0.{}", n + 1),
+                    artifact: sha256,
+                    config: serde_json::json!({ "slug": "hello" }),
+                    provenance: None,
+                },
+            )
+            .unwrap();
+        }
+        api
+    }
+
+    fn slugs(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn status_shows_the_serving_deployment() {
+        let api = deployed(2);
+        let statuses = show_status(&api, &slugs(&["hello", "other"]), false).unwrap();
+
+        assert_eq!(statuses[0].status, "running");
+        assert_eq!(statuses[0].deployment.as_ref().unwrap().id, "dep-2");
+        assert_eq!(statuses[1].status, "not deployed");
+        assert!(statuses[1].deployment.is_none());
+    }
+
+    #[test]
+    fn list_shows_the_most_recent_deployment_first() {
+        let api = deployed(3);
+        let deployments = show_deployments(&api, &slugs(&["hello"]), true).unwrap();
+
+        let ids: Vec<&str> = deployments.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["dep-3", "dep-2", "dep-1"]);
+    }
+
+    #[test]
+    fn rollback_serves_the_previous_deployment() {
+        let api = deployed(2);
+        let deployment = rollback_app(&api, "hello", "dep-1", false).unwrap();
+
+        assert_eq!(deployment.version, "0.0.1");
+        let statuses = show_status(&api, &slugs(&["hello"]), true).unwrap();
+        assert_eq!(statuses[0].deployment.as_ref().unwrap().id, "dep-1");
+    }
+
+    #[test]
+    fn rollback_to_an_unknown_deployment_fails() {
+        let api = deployed(1);
+        assert!(rollback_app(&api, "hello", "dep-9", false).is_err());
+        assert_eq!(api.serving.borrow()["hello"], "dep-1");
+    }
+
+    #[test]
+    fn logs_continue_from_the_cursor() {
+        let api = deployed(1);
+        for message in ["started", "stored a value"] {
+            api.logs.borrow_mut().push(LogEntry {
+                timestamp: "2026-01-01T00:00:00Z".to_string(),
+                level: "info".to_string(),
+                message: message.to_string(),
+                deployment: Some("dep-1".to_string()),
+            });
+        }
+
+        let mut position = LogPosition::default();
+        assert_eq!(show_logs(&api, "hello", &mut position, false).unwrap(), 1);
+        assert_eq!(show_logs(&api, "hello", &mut position, false).unwrap(), 1);
+        assert_eq!(show_logs(&api, "hello", &mut position, true).unwrap(), 0);
+        assert_eq!(position.cursor.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn logs_without_a_cursor_are_not_printed_twice() {
+        let (url, _requests) = serve(vec![
+            (
+                200,
+                r#"{"entries":[
+                    {"timestamp":"2026-01-01T00:00:00Z","message":"started"},
+                    {"timestamp":"2026-01-01T00:00:01Z","message":"first"}
+                ]}"#,
+            ),
+            (
+                200,
+                r#"{"entries":[
+                    {"timestamp":"2026-01-01T00:00:01Z","message":"first"},
+                    {"timestamp":"2026-01-01T00:00:01Z","message":"second"},
+                    {"timestamp":"2026-01-01T00:00:02Z","message":"third"}
+                ]}"#,
+            ),
+            (
+                200,
+                r#"{"entries":[
+                    {"timestamp":"2026-01-01T00:00:01Z","message":"second"},
+                    {"timestamp":"2026-01-01T00:00:02Z","message":"third"}
+                ]}"#,
+            ),
+        ]);
+        let client = HttpClient::new(url, None);
+
+        let mut position = LogPosition::default();
+        assert_eq!(show_logs(&client, "hello", &mut position, true).unwrap(), 2);
+        assert_eq!(show_logs(&client, "hello", &mut position, true).unwrap(), 2);
+        assert_eq!(show_logs(&client, "hello", &mut position, true).unwrap(), 0);
+        assert!(position.cursor.is_none());
+    }
+}
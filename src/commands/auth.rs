@@ -0,0 +1,320 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::util::api::{self, AuthApi, DeviceCode, DeviceLogin, HttpClient};
+use crate::util::credentials::{self, Account, Credentials, TokenSource};
+
+/// Try to open a link in the default browser
+fn open_browser(url: &str) -> bool {
+    let (command, args): (&str, Vec<&str>) = if cfg!(target_os = "macos") {
+        ("open", vec![url])
+    } else if cfg!(target_os = "windows") {
+        ("cmd", vec!["/C", "start", "", url])
+    } else {
+        ("xdg-open", vec![url])
+    };
+
+    Command::new(command)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Obtain a token through a device login approved in the browser
+fn device_login(client: &HttpClient) -> Result<String> {
+    let device = client.start_device_login()?;
+    let link = device
+        .verification_uri_complete
+        .clone()
+        .unwrap_or_else(|| device.verification_uri.clone());
+
+    println!(
+        "\nTo log in, open {} and confirm the code {}",
+        link.cyan(),
+        device.user_code.bold()
+    );
+    if open_browser(&link) {
+        println!("Opened the link in your browser");
+    }
+
+    wait_for_approval(client, &device)
+}
+
+/// Poll a device login until it is approved, denied or expires
+fn wait_for_approval(client: &impl AuthApi, device: &DeviceCode) -> Result<String> {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+            .template("{spinner:.blue} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message("Waiting for confirmation");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval.max(1));
+
+    loop {
+        if Instant::now() > deadline {
+            spinner.finish_and_clear();
+            return Err(anyhow!("The login request expired, try again"));
+        }
+
+        thread::sleep(interval);
+        match client.poll_device_login(&device.device_code) {
+            Ok(DeviceLogin::Complete(token)) => {
+                spinner.finish_and_clear();
+                return Ok(token);
+            }
+            Ok(DeviceLogin::Pending) => {}
+            Ok(DeviceLogin::SlowDown) => interval += Duration::from_secs(5),
+            Err(error) => {
+                spinner.finish_and_clear();
+                return Err(error);
+            }
+        }
+    }
+}
+
+/// Log in and store the token in the user config directory
+pub fn login(api_url: Option<String>, with_token: bool) -> Result<()> {
+    let base_url = api::base_url(api_url.as_deref());
+
+    let token = if with_token {
+        read_token(io::stdin())?
+    } else {
+        device_login(&HttpClient::new(base_url.clone(), None))?
+    };
+
+    let credentials = verify_token(&base_url, token)?;
+    let path = credentials::save(&credentials)?;
+
+    println!(
+        "\n{} {} on {}",
+        "Logged in as".green(),
+        credentials.account.email.bold(),
+        base_url
+    );
+    println!("Credentials saved to {}", path.display());
+    Ok(())
+}
+
+/// Read the token given with `--with-token`
+fn read_token(mut input: impl Read) -> Result<String> {
+    let mut token = String::new();
+    input.read_to_string(&mut token)?;
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        return Err(anyhow!("No token given on stdin"));
+    }
+    Ok(token)
+}
+
+/// Check a token with the API before it is stored
+fn verify_token(base_url: &str, token: String) -> Result<Credentials> {
+    let account = HttpClient::new(base_url.to_string(), Some(token.clone()))
+        .whoami()
+        .map_err(|e| anyhow!("Could not verify the token: {}", e))?;
+
+    Ok(Credentials {
+        api_url: base_url.to_string(),
+        token,
+        account,
+    })
+}
+
+/// Remove the stored token
+pub fn logout() -> Result<()> {
+    if credentials::clear()? {
+        println!("Logged out");
+    } else {
+        println!("Not logged in");
+    }
+
+    if std::env::var("KLAVE_TOKEN").is_ok() {
+        println!(
+            "{}",
+            "Note: KLAVE_TOKEN is set and will still be used".yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Show the account the current token belongs to
+pub fn whoami(api_url: Option<String>) -> Result<()> {
+    let base_url = api::base_url(api_url.as_deref());
+    let (account, source) = current_account(&base_url, credentials::token(&base_url)?)?;
+
+    println!("{}", account.email.bold());
+    if let Some(name) = &account.name {
+        println!("  Name:    {}", name);
+    }
+    println!("  Account: {}", account.id);
+    println!("  API:     {}", base_url);
+    println!(
+        "  Token:   {}",
+        match source {
+            TokenSource::Environment => "KLAVE_TOKEN".to_string(),
+            TokenSource::File(path) => path.display().to_string(),
+        }
+    );
+    Ok(())
+}
+
+/// Account the token selected for an API belongs to
+fn current_account(
+    base_url: &str,
+    token: Option<(String, TokenSource)>,
+) -> Result<(Account, TokenSource)> {
+    let Some((token, source)) = token else {
+        return Err(anyhow!(
+            "Not logged in to {}, run 'klave login' or set KLAVE_TOKEN",
+            base_url
+        ));
+    };
+
+    let account = HttpClient::new(base_url.to_string(), Some(token)).whoami()?;
+    Ok((account, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::api::server::{header, serve};
+    use std::path::PathBuf;
+
+    const ACCOUNT: &str = r#"{"id":"acc-1","email":"dev@example.com","name":"Dev"}"#;
+
+    fn stored(api_url: &str, token: &str) -> Result<Option<(Credentials, PathBuf)>> {
+        Ok(Some((
+            Credentials {
+                api_url: api_url.to_string(),
+                token: token.to_string(),
+                account: serde_json::from_str(ACCOUNT)?,
+            },
+            PathBuf::from("credentials.json"),
+        )))
+    }
+
+    #[test]
+    fn device_login_polls_until_approved() {
+        let (url, requests) = serve(vec![
+            (400, r#"{"error":"authorization_pending"}"#),
+            (200, r#"{"token":"device-token"}"#),
+        ]);
+        let device = DeviceCode {
+            device_code: "dev-code".to_string(),
+            user_code: "ABCD-EFGH".to_string(),
+            verification_uri: format!("{}/device", url),
+            verification_uri_complete: None,
+            interval: 1,
+            expires_in: 60,
+        };
+
+        let token = wait_for_approval(&HttpClient::new(url, None), &device).unwrap();
+        assert_eq!(token, "device-token");
+        for head in requests.iter().take(2) {
+            assert_eq!(head[0], "POST /v1/auth/device/token HTTP/1.1");
+        }
+    }
+
+    #[test]
+    fn device_login_stops_when_denied() {
+        let (url, _requests) = serve(vec![(400, r#"{"error":"access_denied"}"#)]);
+        let device = DeviceCode {
+            device_code: "dev-code".to_string(),
+            user_code: "ABCD-EFGH".to_string(),
+            verification_uri: format!("{}/device", url),
+            verification_uri_complete: None,
+            interval: 1,
+            expires_in: 60,
+        };
+
+        let error = wait_for_approval(&HttpClient::new(url, None), &device)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "The login request was denied");
+    }
+
+    #[test]
+    fn with_token_reads_and_verifies_the_token() {
+        let token = read_token(" ci-token\n".as_bytes()).unwrap();
+        assert!(read_token("\n".as_bytes()).is_err());
+
+        let (url, requests) = serve(vec![(200, ACCOUNT)]);
+        let credentials = verify_token(&url, token).unwrap();
+        assert_eq!(credentials.api_url, url);
+        assert_eq!(credentials.token, "ci-token");
+        assert_eq!(credentials.account.email, "dev@example.com");
+
+        let head = requests.recv().unwrap();
+        assert_eq!(head[0], "GET /v1/me HTTP/1.1");
+        assert_eq!(header(&head, "Authorization"), Some("Bearer ci-token"));
+    }
+
+    #[test]
+    fn rejected_tokens_are_not_stored() {
+        let (url, _requests) = serve(vec![(401, "")]);
+        let error = verify_token(&url, "bad".to_string()).err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Could not verify the token: Not authenticated")
+        );
+    }
+
+    #[test]
+    fn whoami_uses_the_stored_token_of_the_api() {
+        let (url, requests) = serve(vec![(200, ACCOUNT)]);
+        let token = credentials::select_token(None, &url, || stored(&url, "stored")).unwrap();
+
+        let (account, source) = current_account(&url, token).unwrap();
+        assert_eq!(account.id, "acc-1");
+        assert_eq!(account.name.as_deref(), Some("Dev"));
+        assert!(matches!(source, TokenSource::File(_)));
+        assert_eq!(
+            header(&requests.recv().unwrap(), "Authorization"),
+            Some("Bearer stored")
+        );
+    }
+
+    #[test]
+    fn whoami_prefers_klave_token() {
+        let (url, requests) = serve(vec![(200, ACCOUNT)]);
+        let token = credentials::select_token(Some("from-env".to_string()), &url, || {
+            stored(&url, "stored")
+        })
+        .unwrap();
+
+        let (_, source) = current_account(&url, token).unwrap();
+        assert!(matches!(source, TokenSource::Environment));
+        assert_eq!(
+            header(&requests.recv().unwrap(), "Authorization"),
+            Some("Bearer from-env")
+        );
+    }
+
+    #[test]
+    fn stored_token_is_not_sent_to_another_api() {
+        let (url, requests) = serve(vec![(200, ACCOUNT)]);
+        let token =
+            credentials::select_token(None, &url, || stored("https://api.klave.com", "stored"))
+                .unwrap();
+
+        let error = current_account(&url, token).err().unwrap();
+        assert!(error.to_string().starts_with("Not logged in to"));
+        assert!(
+            requests
+                .recv_timeout(std::time::Duration::from_millis(200))
+                .is_err()
+        );
+    }
+}
@@ -3,19 +3,27 @@ use colored::*;
 use dialoguer::{Confirm, theme::ColorfulTheme};
 use indicatif::{ProgressBar, ProgressStyle};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::time::{Duration, Instant};
 
+use crate::commands::reproducible;
+use crate::util::wasm::format_size;
+use crate::util::{branches, environment, host_api, keys, manifest, optimize, project, provenance};
+
 const KLAVE_CYAN_BG: &str = "Klave - The honest-by-design platform";
 
+const ARTIFACT_SIZES_FILE: &str = ".klave/build-sizes.json";
+
 struct BuildResult {
     app: String,
     success: bool,
     app_type: String,
     time: Duration,
+    size: Option<usize>,
 }
 
 /// Check if a command is available in the PATH
@@ -47,13 +55,12 @@ fn resolve_package_manager() -> String {
     let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
 
     if Path::new(&cwd).join("yarn.lock").exists() {
-        return "yarn".to_string();
+        "yarn".to_string()
     } else if Path::new(&cwd).join("pnpm-lock.yaml").exists() {
-        return "pnpm".to_string();
-    } else if Path::new(&cwd).join("package-lock.json").exists() {
-        return "npm".to_string();
+        "pnpm".to_string()
     } else {
-        return "npm".to_string(); // Default to npm
+        // package-lock.json or no lock file, default to npm
+        "npm".to_string()
     }
 }
 
@@ -61,11 +68,7 @@ fn resolve_package_manager() -> String {
 fn are_dependencies_installed(cwd: &Path) -> bool {
     // Basic check for node_modules directory existence
     let node_modules = cwd.join("node_modules");
-    if !node_modules.exists() {
-        return false;
-    } else {
-        return true;
-    }
+    node_modules.exists()
 }
 
 /// Install dependencies using the detected package manager
@@ -96,7 +99,79 @@ fn install_dependencies(cwd: &Path, package_manager: &str) -> Result<bool> {
     }
 }
 
-/// Run command and capture output
+/// Copy the built artifacts to the dist directory and write their signed manifest
+fn sign_artifacts(
+    cwd: &Path,
+    applications: &[&Value],
+    dist: &Path,
+    key_name: &str,
+) -> Result<PathBuf> {
+    let key = keys::load_or_create(key_name)?;
+
+    let artifacts = applications
+        .iter()
+        .map(|application| manifest::add_artifact(cwd, application, dist))
+        .collect::<Result<Vec<_>>>()?;
+
+    manifest::write(dist, artifacts, Vec::new(), Some(&key))
+}
+
+/// Command building an application, as (program, arguments, working directory)
+pub fn build_command<'a>(
+    app_type: &str,
+    package_manager: &str,
+    app_slug: &'a str,
+    cwd: &Path,
+    app_dir: &Path,
+) -> (&'static str, Vec<&'a str>, PathBuf) {
+    if app_type == "rust" {
+        return (
+            "cargo",
+            vec![
+                "component",
+                "build",
+                "--target",
+                "wasm32-unknown-unknown",
+                "--release",
+            ],
+            app_dir.to_path_buf(),
+        );
+    }
+
+    let (command, args) = match package_manager {
+        "npm" => ("npm", vec!["run", "build", "--", "--app", app_slug]),
+        "yarn" => ("yarn", vec!["build", "--app", app_slug]),
+        "pnpm" => ("pnpm", vec!["build", "--app", app_slug]),
+        _ => ("npm", vec!["run", "build"]),
+    };
+    (command, args, cwd.to_path_buf())
+}
+
+/// Build profile recorded in the provenance of an application
+///
+/// The cargo profile the build command selects for Rust apps, the build script
+/// of package.json for AssemblyScript apps, which decides the compiler flags.
+pub fn build_profile(app_type: &str, args: &[&str], cwd: &Path) -> String {
+    if app_type == "rust" {
+        let profile = if args.contains(&"--release") {
+            "release"
+        } else {
+            "dev"
+        };
+        return profile.to_string();
+    }
+
+    fs::read_to_string(cwd.join("package.json"))
+        .ok()
+        .and_then(|manifest| serde_json::from_str::<Value>(&manifest).ok())
+        .and_then(|manifest| {
+            let script = manifest.get("scripts")?.get("build")?.as_str()?;
+            Some(format!("build script \"{}\"", script))
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Run a command, failing when it does not exit successfully
 async fn run_command(
     command: &str,
     args: &[&str],
@@ -111,74 +186,172 @@ async fn run_command(
             .stderr(std::process::Stdio::inherit());
     }
 
-    cmd.output()
-        .context(format!("Failed to execute command: {} {:?}", command, args))
-}
-
-/// Main build command implementation
-pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> Result<()> {
-    // Get current working directory
-    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let output = cmd
+        .output()
+        .context(format!("Failed to execute command: {} {:?}", command, args))?;
 
-    // Check if klave.json exists
-    let klave_config_path = cwd.join("klave.json");
-    if !klave_config_path.exists() {
+    // A failed build must not let the previous artifact through
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(anyhow!(
-            "Error: klave.json file not found. Make sure you are in a Klave project directory. Run 'klave create' to start a new project."
+            "{} {} {}{}",
+            command,
+            args.join(" "),
+            match output.status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => "was terminated by a signal".to_string(),
+            },
+            if stderr.trim().is_empty() {
+                String::new()
+            } else {
+                format!(": {}", stderr.trim())
+            }
         ));
     }
+    Ok(output)
+}
 
-    // Read the klave config
-    let klave_config_str =
-        fs::read_to_string(&klave_config_path).context("Failed to read klave.json")?;
+/// Load the artifact sizes recorded by the previous build
+fn load_artifact_sizes(cwd: &Path) -> HashMap<String, usize> {
+    fs::read_to_string(cwd.join(ARTIFACT_SIZES_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
 
-    let klave_config: Value =
-        serde_json::from_str(&klave_config_str).context("Invalid JSON in klave.json")?;
+/// Record artifact sizes so the next build can report size changes
+fn save_artifact_sizes(cwd: &Path, sizes: &HashMap<String, usize>) -> Result<()> {
+    let path = cwd.join(ARTIFACT_SIZES_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(sizes)?)
+        .context(format!("Failed to write {:?}", path))
+}
 
-    // Get applications array
-    let applications = klave_config
-        .get("applications")
-        .and_then(|v| v.as_array())
-        .ok_or_else(|| {
-            anyhow!("Error: Invalid klave.json file structure. 'applications' array not found.")
-        })?;
+/// Get the size of the artifact of an application, if it can be found
+fn measure_artifact(cwd: &Path, application: &Value) -> Result<Option<usize>> {
+    let artifact = project::artifact_path(cwd, application)?;
+    Ok(fs::metadata(&artifact).ok().map(|m| m.len() as usize))
+}
 
-    // Filter applications based on app argument
-    let apps_to_process = if let Some(app_name) = &app {
-        let filtered = applications
-            .iter()
-            .filter(|a| {
-                a.get("slug").and_then(|s| s.as_str()) == Some(app_name)
-                    || a.get("name").and_then(|s| s.as_str()) == Some(app_name)
-            })
-            .collect::<Vec<_>>();
-
-        if filtered.is_empty() {
-            // List available apps if the specified app wasn't found
-            let available_apps: Vec<String> = applications
-                .iter()
-                .filter_map(|a| {
-                    a.get("slug")
-                        .and_then(|s| s.as_str())
-                        .or_else(|| a.get("name").and_then(|s| s.as_str()))
-                })
-                .map(|s| s.to_string())
-                .collect();
-
-            return Err(anyhow!(
-                "Error: No application found with name \"{}\". Available applications: {}",
-                app_name,
-                available_apps.join(", ")
-            ));
+/// Describe how an artifact size changed since the previous build
+fn size_change(size: usize, previous: Option<usize>) -> String {
+    match previous {
+        None => "no previous build".to_string(),
+        Some(previous) if previous == size => "unchanged since last build".to_string(),
+        Some(previous) if size > previous => {
+            format!("+{} since last build", format_size(size - previous))
         }
+        Some(previous) => format!("-{} since last build", format_size(previous - size)),
+    }
+}
 
-        filtered
-    } else {
-        applications.iter().collect::<Vec<_>>()
+/// Check an artifact against the `maxSize` budget of its application
+fn check_size_budget(
+    application: &Value,
+    size: Option<usize>,
+    previous: Option<usize>,
+) -> Result<()> {
+    let Some(max_size) = project::max_size(application)? else {
+        return Ok(());
+    };
+
+    let Some(size) = size else {
+        return Err(anyhow!(
+            "Artifact not found, cannot check the maxSize budget of {}",
+            format_size(max_size)
+        ));
+    };
+
+    if size > max_size {
+        return Err(anyhow!(
+            "Artifact is {} ({} bytes), exceeding the maxSize budget of {} ({} bytes) by {} ({})",
+            format_size(size),
+            size,
+            format_size(max_size),
+            max_size,
+            format_size(size - max_size),
+            size_change(size, previous)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Outcome of the post-processing of an artifact
+pub struct PostProcessed {
+    /// Optimisation level and report, when optimisation is enabled
+    pub optimize_report: Option<(String, optimize::OptimizeReport)>,
+    pub host_warnings: Vec<String>,
+}
+
+/// Turn the compiler output of an application into its deployable artifact
+///
+/// The output is copied to `.klave/dist` so an artifact the compiler did not
+/// rebuild is never processed twice, then optimised when enabled, checked
+/// against the host API and stamped with its provenance.
+pub fn post_process(
+    cwd: &Path,
+    application: &Value,
+    optimize: Option<&str>,
+    provenance: &provenance::Provenance,
+) -> Result<PostProcessed> {
+    let output = project::compiler_output(cwd, application)?;
+    let artifact = project::artifact_path(cwd, application)?;
+    if !output.exists() {
+        return Err(anyhow!("No artifact found at {:?} after the build", output));
+    }
+    if let Some(parent) = artifact.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(&output, &artifact)
+        .context(format!("Failed to copy {:?} to {:?}", output, artifact))?;
+
+    let optimize_report = match optimize::options_for(application, optimize)? {
+        Some(options) => Some((
+            options.level.clone(),
+            optimize::optimize_artifact(&artifact, &options)?,
+        )),
+        None => None,
     };
 
-    if apps_to_process.is_empty() {
-        return Err(anyhow!("Error: No applications found in klave.json"));
+    let host_warnings = host_api::check_artifact(cwd, application, &artifact)?;
+    provenance::embed(&artifact, provenance)?;
+
+    Ok(PostProcessed {
+        optimize_report,
+        host_warnings,
+    })
+}
+
+/// Main build command implementation
+#[allow(clippy::too_many_arguments)]
+pub async fn execute(
+    app: Option<String>,
+    skip_checks: bool,
+    verbose: bool,
+    optimize: Option<String>,
+    verify_reproducible: bool,
+    sign: Option<String>,
+    branch_aware: bool,
+    env_name: Option<String>,
+) -> Result<()> {
+    // Get current working directory
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+
+    // Read the klave config, with the environment overlay if any
+    let klave_config = environment::resolve(&cwd, env_name.as_deref())?;
+    let applications = project::applications(&klave_config)?;
+
+    // Filter applications based on app argument
+    let mut apps_to_process = project::select_apps(applications, app.as_deref())?;
+
+    // Apply per-branch settings and warn about branches that won't deploy
+    let branch_apps;
+    if branch_aware {
+        branch_apps = branches::resolve_apps(&cwd, &klave_config, &apps_to_process, false)?;
+        apps_to_process = branch_apps.iter().collect();
     }
 
     println!("\n");
@@ -221,25 +394,13 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
         let mut missing_tools = Vec::new();
 
         // Check if apps need specific tools
-        let needs_rust = apps_to_process.iter().any(|app| {
-            let root_dir = app.get("rootDir").and_then(|s| s.as_str()).unwrap_or(".");
-            let app_dir = if root_dir.starts_with('/') {
-                cwd.join(&root_dir[1..])
-            } else {
-                cwd.join(root_dir)
-            };
-            app_dir.join("Cargo.toml").exists()
-        });
+        let needs_rust = apps_to_process
+            .iter()
+            .any(|app| project::app_dir(&cwd, app).join("Cargo.toml").exists());
 
-        let needs_assemblyscript = apps_to_process.iter().any(|app| {
-            let root_dir = app.get("rootDir").and_then(|s| s.as_str()).unwrap_or(".");
-            let app_dir = if root_dir.starts_with('/') {
-                cwd.join(&root_dir[1..])
-            } else {
-                cwd.join(root_dir)
-            };
-            app_dir.join("tsconfig.json").exists()
-        });
+        let needs_assemblyscript = apps_to_process
+            .iter()
+            .any(|app| project::app_dir(&cwd, app).join("tsconfig.json").exists());
 
         if needs_rust && !has_cargo {
             missing_tools.push("Rust toolchain (install from https://rustup.rs/)");
@@ -293,15 +454,9 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
 
     // Check if dependencies are installed for AssemblyScript projects
     let needs_dependencies = has_package_json
-        && apps_to_process.iter().any(|app| {
-            let root_dir = app.get("rootDir").and_then(|s| s.as_str()).unwrap_or(".");
-            let app_dir = if root_dir.starts_with('/') {
-                cwd.join(&root_dir[1..])
-            } else {
-                cwd.join(root_dir)
-            };
-            app_dir.join("tsconfig.json").exists()
-        });
+        && apps_to_process
+            .iter()
+            .any(|app| project::app_dir(&cwd, app).join("tsconfig.json").exists());
 
     if needs_dependencies && !are_dependencies_installed(&cwd) {
         spinner.finish_with_message("Project analysis complete");
@@ -353,27 +508,28 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
         spinner.finish_with_message("Project analysis complete");
     }
 
+    if verify_reproducible {
+        return reproducible::execute(
+            &cwd,
+            &apps_to_process,
+            &package_manager,
+            optimize.as_deref(),
+        );
+    }
+
     // Track build status for summary
     let mut build_results: Vec<BuildResult> = Vec::new();
 
+    // Artifact sizes from the previous build, to report size changes
+    let mut artifact_sizes = load_artifact_sizes(&cwd);
+
+    // Successfully built applications, listed in the signed manifest
+    let mut built_apps: Vec<&Value> = Vec::new();
+
     // Build each application
     for application in apps_to_process {
-        let app_slug = application
-            .get("slug")
-            .and_then(|s| s.as_str())
-            .or_else(|| application.get("name").and_then(|s| s.as_str()))
-            .unwrap_or("unknown");
-
-        let root_dir = application
-            .get("rootDir")
-            .and_then(|s| s.as_str())
-            .unwrap_or(".");
-
-        let app_dir = if root_dir.starts_with('/') {
-            cwd.join(&root_dir[1..])
-        } else {
-            cwd.join(root_dir)
-        };
+        let app_slug = project::app_slug(application);
+        let app_dir = project::app_dir(&cwd, application);
 
         if !app_dir.exists() {
             eprintln!(
@@ -389,18 +545,12 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                 success: false,
                 app_type: "unknown".to_string(),
                 time: Duration::from_secs(0),
+                size: None,
             });
             continue;
         }
 
-        // Determine app type - simplified to just rust or assemblyscript
-        let app_type = if app_dir.join("Cargo.toml").exists() {
-            "rust"
-        } else if app_dir.join("tsconfig.json").exists() {
-            "assemblyscript"
-        } else {
-            "unknown"
-        };
+        let app_type = project::app_type(&app_dir);
 
         if app_type == "unknown" {
             eprintln!(
@@ -412,6 +562,7 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                 success: false,
                 app_type: app_type.to_string(),
                 time: Duration::from_secs(0),
+                size: None,
             });
             continue;
         }
@@ -419,6 +570,8 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
         let start_time = Instant::now();
         spinner.set_message(format!("Building {} app \"{}\"", app_type, app_slug));
 
+        let (command, args, dir) =
+            build_command(app_type, &package_manager, app_slug, &cwd, &app_dir);
         let build_result = match app_type {
             "rust" => {
                 // Check if Rust tools are available
@@ -432,20 +585,7 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                     ))
                 } else {
                     // Build Rust application
-                    run_command(
-                        "cargo",
-                        &[
-                            "component",
-                            "build",
-                            "--target",
-                            "wasm32-unknown-unknown",
-                            "--release",
-                        ],
-                        &app_dir,
-                        true,
-                    )
-                    .await
-                    .map(|_| ())
+                    run_command(command, &args, &dir, true).await.map(|_| ())
                 }
             }
             "assemblyscript" => {
@@ -460,16 +600,7 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                     ))
                 } else {
                     // Build AssemblyScript application
-                    let (build_command, build_args) = match package_manager.as_str() {
-                        "npm" => ("npm", vec!["run", "build", "--", "--app", app_slug]),
-                        "yarn" => ("yarn", vec!["build", "--app", app_slug]),
-                        "pnpm" => ("pnpm", vec!["build", "--app", app_slug]),
-                        _ => ("npm", vec!["run", "build"]),
-                    };
-
-                    run_command(build_command, &build_args, &cwd, true)
-                        .await
-                        .map(|_| ())
+                    run_command(command, &args, &dir, true).await.map(|_| ())
                 }
             }
             _ => Err(anyhow!("Unknown app type")),
@@ -477,6 +608,37 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
 
         let elapsed = start_time.elapsed();
 
+        // Turn the compiler output into the deployable artifact
+        let mut processed = None;
+        let build_result = build_result.and_then(|_| {
+            spinner.set_message(format!("Processing the artifact of \"{}\"", app_slug));
+            let profile = build_profile(app_type, &args, &cwd);
+            let provenance = provenance::collect(&cwd, application, profile)?;
+            processed = Some(post_process(
+                &cwd,
+                application,
+                optimize.as_deref(),
+                &provenance,
+            )?);
+            Ok(())
+        });
+        let (optimize_report, host_warnings) = match processed {
+            Some(processed) => (processed.optimize_report, processed.host_warnings),
+            None => (None, Vec::new()),
+        };
+
+        // Measure the artifact and check it against its size budget
+        let mut artifact_size = None;
+        let previous_size = artifact_sizes.get(app_slug).copied();
+        let build_result = build_result.and_then(|_| {
+            artifact_size = measure_artifact(&cwd, application)?;
+            check_size_budget(application, artifact_size, previous_size)
+        });
+
+        if let Some(size) = artifact_size {
+            artifact_sizes.insert(app_slug.to_string(), size);
+        }
+
         match build_result {
             Ok(_) => {
                 spinner.finish_with_message(
@@ -490,11 +652,45 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                     .to_string(),
                 );
 
+                if let Some((level, report)) = &optimize_report {
+                    let saved = report.before as f64 - report.after as f64;
+                    let pass = match (&report.skipped_reason, report.stripped) {
+                        (None, _) => format!("Optimized (O{})", level),
+                        (Some(reason), true) => format!("Sections stripped ({})", reason),
+                        (Some(reason), false) => format!("Not optimized ({})", reason),
+                    };
+                    println!(
+                        "  {}: {} -> {} ({:.1}% smaller)",
+                        pass,
+                        format_size(report.before),
+                        format_size(report.after),
+                        if report.before > 0 {
+                            saved * 100.0 / report.before as f64
+                        } else {
+                            0.0
+                        }
+                    );
+                }
+
+                for warning in &host_warnings {
+                    println!("  {}", format!("Warning: {}", warning).yellow());
+                }
+
+                if let Some(size) = artifact_size {
+                    println!(
+                        "  Size: {} ({})",
+                        format_size(size),
+                        size_change(size, previous_size)
+                    );
+                }
+
+                built_apps.push(application);
                 build_results.push(BuildResult {
                     app: app_slug.to_string(),
                     success: true,
                     app_type: app_type.to_string(),
                     time: elapsed,
+                    size: artifact_size,
                 });
             }
             Err(error) => {
@@ -547,11 +743,36 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
                     success: false,
                     app_type: app_type.to_string(),
                     time: elapsed,
+                    size: artifact_size,
                 });
             }
         }
     }
 
+    if let Err(error) = save_artifact_sizes(&cwd, &artifact_sizes) {
+        eprintln!(
+            "{}",
+            format!("Warning: Could not record artifact sizes: {}", error).yellow()
+        );
+    }
+
+    if let Some(key_name) = &sign {
+        if built_apps.len() == build_results.len() {
+            let dist = cwd.join(project::DIST_DIR);
+            let path = sign_artifacts(&cwd, &built_apps, &dist, key_name)?;
+            println!(
+                "\n{} {}",
+                "Signed manifest written to".green(),
+                path.display()
+            );
+        } else {
+            eprintln!(
+                "{}",
+                "Warning: Not signing the build manifest because some builds failed".yellow()
+            );
+        }
+    }
+
     // Show summary
     let total = build_results.len();
     let successful = build_results.iter().filter(|r| r.success).count();
@@ -575,7 +796,7 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
     if successful > 0 {
         println!("\n{}", "Next steps:".bold());
         println!("  1. {} your application to Klave", "Deploy".green().bold());
-        println!("     Run: {}", "klave deploy (wip)".cyan());
+        println!("     Run: {}", "klave deploy".cyan());
         println!("  2. Test and monitor your application");
         println!("     Visit the Klave platform: https://app.klave.com");
     }
@@ -598,12 +819,18 @@ pub async fn execute(app: Option<String>, skip_checks: bool, verbose: bool) -> R
             "".normal()
         };
 
+        let size = match result.size {
+            Some(size) => format_size(size).dimmed(),
+            None => "".normal(),
+        };
+
         println!(
-            "{} {} [{}] {}",
+            "{} {} [{}] {} {}",
             status,
             result.app.bold(),
             result.app_type,
-            time
+            time,
+            size
         );
     }
 
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde_json::json;
+use std::env;
+
+use crate::util::environment;
+use crate::util::interpolate::Mode;
+
+/// Print the resolved klave.json of an environment and where each value comes from
+///
+/// Variable references are shown as written, so the output can be shared
+/// without leaking values from the environment or `.env`.
+pub fn show(env_name: Option<String>, json: bool) -> Result<()> {
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let env_name = environment::selected(env_name.as_deref());
+    let (config, values) =
+        environment::resolve_with_sources(&cwd, env_name.as_deref(), Mode::Mask)?;
+
+    if json {
+        let sources: serde_json::Map<String, serde_json::Value> = values
+            .into_iter()
+            .map(|(path, _, source)| (path, json!(source)))
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "config": config, "sources": sources }))?
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} ({})\n",
+        "Resolved configuration".bold(),
+        match &env_name {
+            Some(name) => format!("environment \"{}\"", name),
+            None => "no environment".to_string(),
+        }
+    );
+    println!("{}", serde_json::to_string_pretty(&config)?);
+
+    println!("\n{}", "Sources:".bold());
+    let width = values
+        .iter()
+        .map(|(path, _, _)| path.len())
+        .max()
+        .unwrap_or(0);
+    for (path, value, source) in values {
+        println!(
+            "  {:<width$}  {}  {}",
+            path,
+            value,
+            if source == "klave.json" {
+                source.dimmed()
+            } else {
+                source.cyan()
+            },
+            width = width
+        );
+    }
+
+    Ok(())
+}
@@ -53,7 +53,9 @@ Read more here: https://docs.klave.com/quickstart/create").red());
     // Get project directory
     let project_dir = if let Some(d) = dir {
         d
-    } else if name.is_none() {
+    } else if let Some(n) = &name {
+        format!("./{}", n)
+    } else {
         Input::<String>::with_theme(&ColorfulTheme::default())
             .with_prompt("Where should we create your project?")
             .default("./my-honest-app".into())
@@ -65,8 +67,6 @@ Read more here: https://docs.klave.com/quickstart/create").red());
                 Ok(())
             })
             .interact()?
-    } else {
-        format!("./{}", name.as_ref().unwrap())
     };
 
     // Get project name
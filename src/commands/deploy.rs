@@ -0,0 +1,350 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::util::api::{Deployment, DeploymentApi, DeploymentRequest, HttpClient};
+use crate::util::bundle::Bundle;
+use crate::util::interpolate::{self, Mode};
+use crate::util::manifest::Trust;
+use crate::util::wasm::{self, format_size};
+use crate::util::{branches, environment, keys, manifest, project, provenance, secrets};
+
+/// Outcome of deploying one application
+struct DeployResult {
+    app: String,
+    version: String,
+    time: Duration,
+    outcome: Result<(Deployment, Option<usize>)>,
+}
+
+/// Upload an application's artifact if needed and deploy it
+///
+/// Returns the deployment and the number of bytes uploaded, or None when the
+/// API already had the artifact.
+fn deploy_app(
+    cwd: &Path,
+    application: &Value,
+    artifact: &Path,
+    client: &dyn DeploymentApi,
+) -> Result<(Deployment, Option<usize>)> {
+    let app_slug = project::app_slug(application);
+    let bytes = fs::read(artifact).context(format!(
+        "No artifact found at {:?}. Run 'klave build' first.",
+        artifact
+    ))?;
+    let sha256 = manifest::sha256(&bytes);
+
+    let provenance = match provenance::read(&wasm::parse(&bytes)?).transpose()? {
+        Some(provenance) => Some(serde_json::to_value(provenance)?),
+        None => None,
+    };
+
+    let uploaded = if client.has_artifact(app_slug, &sha256)? {
+        println!("  Artifact {} already uploaded", &sha256[..12]);
+        None
+    } else {
+        let progress = ProgressBar::new(bytes.len() as u64);
+        progress.set_style(
+            ProgressStyle::default_bar()
+                .template("  Uploading {bar:30.cyan/blue} {bytes}/{total_bytes}")
+                .unwrap(),
+        );
+        client.upload_artifact(
+            app_slug,
+            &sha256,
+            &mut progress.wrap_read(bytes.as_slice()),
+            bytes.len() as u64,
+        )?;
+        progress.finish_and_clear();
+        println!(
+            "  Uploaded {} ({})",
+            &sha256[..12],
+            format_size(bytes.len())
+        );
+        Some(bytes.len())
+    };
+
+    // Values stay out of the output, only their number is shown. Secrets are
+    // merged, so an app without local secrets keeps the ones set elsewhere,
+    // and the ones removed locally are removed once pushed.
+    if secrets::exists(cwd) {
+        let mut all = secrets::load(cwd)?;
+        let patch = all.patch(app_slug);
+        if !patch.is_empty() {
+            client.set_secrets(app_slug, &patch)?;
+            let removed = patch.values().filter(|value| value.is_none()).count();
+            if removed > 0 {
+                println!(
+                    "  Pushed {} secret(s), removed {}",
+                    patch.len() - removed,
+                    removed
+                );
+                all.removed.remove(app_slug);
+                secrets::save(cwd, &all)?;
+            } else {
+                println!("  Pushed {} secret(s)", patch.len());
+            }
+        }
+    }
+
+    let deployment = client.create_deployment(
+        app_slug,
+        &DeploymentRequest {
+            version: application
+                .get("version")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            artifact: sha256,
+            config: application.clone(),
+            provenance,
+        },
+    )?;
+
+    Ok((deployment, uploaded))
+}
+
+/// Deploy applications with their artifacts through a deployment API client
+pub fn deploy_apps(
+    cwd: &Path,
+    applications: &[(&Value, PathBuf)],
+    client: &dyn DeploymentApi,
+) -> Result<()> {
+    let mut results = Vec::new();
+
+    for (application, artifact) in applications {
+        let app_slug = project::app_slug(application);
+        println!("\n{} \"{}\"", "Deploying".bold(), app_slug);
+
+        let start = Instant::now();
+        let outcome = deploy_app(cwd, application, artifact, client);
+        if let Err(error) = &outcome {
+            eprintln!("  {}", format!("Error: {}", error).red());
+        }
+
+        results.push(DeployResult {
+            app: app_slug.to_string(),
+            version: application
+                .get("version")
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            time: start.elapsed(),
+            outcome,
+        });
+    }
+
+    println!("\n{}", "Deployment summary:".bold());
+    for result in &results {
+        match &result.outcome {
+            Ok((deployment, uploaded)) => {
+                println!(
+                    "{} {} {} {} [{}] {}",
+                    "✓".green(),
+                    result.app.bold(),
+                    result.version,
+                    deployment.id,
+                    deployment.status,
+                    match uploaded {
+                        Some(size) => format!("uploaded {}", format_size(*size)),
+                        None => "artifact unchanged".to_string(),
+                    }
+                    .dimmed()
+                );
+                if let Some(url) = &deployment.url {
+                    println!("  {}", url.cyan());
+                }
+            }
+            Err(error) => println!(
+                "{} {} {} {}",
+                "✗".red(),
+                result.app.bold(),
+                result.version,
+                format!("{} ({:.2}s)", error, result.time.as_secs_f64()).red()
+            ),
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} deployments failed",
+            failed,
+            results.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Open a bundle to deploy, refusing it unless its signer is trusted
+///
+/// The manifest must be signed with `public_key`, or with a local key when
+/// none is given. Unsigned bundles are only deployed with `allow_unsigned`.
+fn open_trusted_bundle(
+    path: &str,
+    public_key: Option<&str>,
+    allow_unsigned: bool,
+) -> Result<Bundle> {
+    let bundle = Bundle::open(Path::new(path))?;
+    if bundle.manifest.signature.is_none() {
+        if !allow_unsigned {
+            return Err(anyhow!(
+                "The bundle {} is not signed. Sign it with 'klave pack --sign' or pass --allow-unsigned",
+                path
+            ));
+        }
+        println!("{}", "Deploying an unsigned bundle".yellow());
+        return Ok(bundle);
+    }
+
+    let (signature, trust) = manifest::verify_trusted(&bundle.manifest, public_key)?;
+    println!(
+        "{} signed by \"{}\" (key {}), {}",
+        "Bundle".green(),
+        signature.key_name,
+        keys::fingerprint(&signature.public_key),
+        match trust {
+            Trust::Expected => "the expected public key".to_string(),
+            Trust::LocalKey(name) => format!("your local key \"{}\"", name),
+        }
+    );
+    Ok(bundle)
+}
+
+/// Main deploy command implementation
+pub fn execute(
+    app: Option<String>,
+    env_name: Option<String>,
+    bundle_path: Option<String>,
+    public_key: Option<String>,
+    allow_unsigned: bool,
+    api_url: Option<String>,
+) -> Result<()> {
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+
+    // A bundle carries its resolved klave.json, with the environment overlay
+    // and branch rules applied when it was packed, so KLAVE_ENV is ignored.
+    // Its variable references are expanded here, from the environment and
+    // .env of the deploying machine.
+    if bundle_path.is_some() && env_name.is_some() {
+        return Err(anyhow!(
+            "--env cannot be used with --bundle, the environment is applied by 'klave pack --env'"
+        ));
+    }
+    let bundle = match &bundle_path {
+        Some(path) => {
+            let bundle = open_trusted_bundle(path, public_key.as_deref(), allow_unsigned)?;
+            println!(
+                "{}",
+                "The environment and branch rules were applied when the bundle was packed, variables are expanded from this environment"
+                    .dimmed()
+            );
+            Some(bundle)
+        }
+        None => None,
+    };
+    let resolved = match &bundle {
+        Some(bundle) => {
+            let mut config = bundle.config.clone();
+            interpolate::resolve(&cwd, &mut config, Mode::Expand)?;
+            let applications = project::applications(&config)?;
+            project::select_apps(applications, app.as_deref())?
+                .into_iter()
+                .cloned()
+                .collect()
+        }
+        None => {
+            let klave_config = environment::resolve(&cwd, env_name.as_deref())?;
+            let applications = project::applications(&klave_config)?;
+            let selected = project::select_apps(applications, app.as_deref())?;
+            branches::resolve_apps(&cwd, &klave_config, &selected, true)?
+        }
+    };
+
+    let apps_to_deploy = resolved
+        .iter()
+        .map(|application| {
+            let artifact = match &bundle {
+                Some(bundle) => bundle.artifact_path(project::app_slug(application))?,
+                None => project::artifact_path(&cwd, application)?,
+            };
+            Ok((application, artifact))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let client = HttpClient::configured(api_url.as_deref())?;
+    println!(
+        "Deploying {}{} to {}",
+        match &app {
+            Some(app_name) => format!("application \"{}\"", app_name),
+            None => format!("{} applications", apps_to_deploy.len()),
+        },
+        match &bundle_path {
+            Some(path) => format!(" from {}", path),
+            None => String::new(),
+        },
+        client.base_url()
+    );
+
+    deploy_apps(&cwd, &apps_to_deploy, &client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::api::mock::MockApi;
+    use serde_json::json;
+
+    fn application() -> Value {
+        json!({ "slug": "hello", "version": "0.0.1" })
+    }
+
+    /// Empty core module
+    fn artifact(dir: &Path) -> PathBuf {
+        let path = dir.join("hello.wasm");
+        fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+        path
+    }
+
+    #[test]
+    fn deploy_uploads_the_artifact_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let application = application();
+        let apps = [(&application, artifact(dir.path()))];
+        let api = MockApi::default();
+
+        deploy_apps(dir.path(), &apps, &api).unwrap();
+        deploy_apps(dir.path(), &apps, &api).unwrap();
+
+        assert_eq!(api.artifacts.borrow().len(), 1);
+        let deployments = api.deployments.borrow();
+        assert_eq!(deployments.len(), 2);
+        assert_eq!(deployments[1].version, "0.0.1");
+        assert_eq!(
+            deployments[1].artifact,
+            manifest::sha256(&fs::read(&apps[0].1).unwrap())
+        );
+    }
+
+    #[test]
+    fn deploy_reports_failed_apps() {
+        let dir = tempfile::tempdir().unwrap();
+        let application = application();
+        let missing = json!({ "slug": "missing", "version": "0.0.1" });
+        let apps = [
+            (&application, artifact(dir.path())),
+            (&missing, dir.path().join("missing.wasm")),
+        ];
+        let api = MockApi::default();
+
+        let error = deploy_apps(dir.path(), &apps, &api).unwrap_err();
+        assert_eq!(error.to_string(), "1 of 2 deployments failed");
+        assert_eq!(api.deployments.borrow().len(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::util::wasm::{self, WasmKind, format_size};
+use crate::util::{environment, project, provenance};
+
+/// Resolve the wasm file to inspect from an app slug or a path
+fn resolve_target(target: &str) -> Result<PathBuf> {
+    let path = Path::new(target);
+    if path.extension().and_then(|e| e.to_str()) == Some("wasm") || path.is_file() {
+        if !path.exists() {
+            return Err(anyhow!("Error: File not found: {:?}", path));
+        }
+        return Ok(path.to_path_buf());
+    }
+
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let klave_config = environment::resolve(&cwd, None)?;
+    let applications = project::applications(&klave_config)?;
+
+    let application = project::find_app(applications, target).ok_or_else(|| {
+        let available_apps: Vec<&str> = applications.iter().map(project::app_slug).collect();
+        anyhow!(
+            "Error: No application found with name \"{}\". Available applications: {}",
+            target,
+            available_apps.join(", ")
+        )
+    })?;
+
+    let artifact = project::artifact_path(&cwd, application)?;
+    if !artifact.exists() {
+        return Err(anyhow!(
+            "Error: No artifact found for \"{}\" at {:?}. Run 'klave build' first.",
+            target,
+            artifact
+        ));
+    }
+
+    Ok(artifact)
+}
+
+/// Main inspect command implementation
+pub fn execute(target: String, top: usize) -> Result<()> {
+    let path = resolve_target(&target)?;
+    let bytes = fs::read(&path).context(format!("Failed to read {:?}", path))?;
+    let info = wasm::parse(&bytes)?;
+
+    println!("\n{} {}", "Inspecting".bold(), path.display());
+    println!("  Kind: {}", info.kind.to_string().cyan());
+    println!("  Size: {} ({} bytes)", format_size(info.size), info.size);
+
+    println!("\n{} ({})", "Imports".bold(), info.imports.len());
+    for import in &info.imports {
+        if import.module.is_empty() {
+            println!("  {} {}", import.name, import.ty.dimmed());
+        } else {
+            println!("  {}.{} {}", import.module, import.name, import.ty.dimmed());
+        }
+    }
+
+    println!("\n{} ({})", "Exports".bold(), info.exports.len());
+    for export in &info.exports {
+        println!("  {} {}", export.name, export.ty.dimmed());
+    }
+
+    if info.kind == WasmKind::Component {
+        println!("\n{}", "WIT world:".bold());
+        match wasm::wit_world(&bytes) {
+            Some(world) => {
+                for line in world.lines() {
+                    println!("{}", format!("  {}", line).trim_end());
+                }
+            }
+            None => println!("  {}", "No WIT metadata found".dimmed()),
+        }
+
+        println!("\n{} ({})", "Core modules".bold(), info.modules.len());
+        for (index, module) in info.modules.iter().enumerate() {
+            println!(
+                "  Module {}: {} imports, {} exports, code {}, data {}",
+                index,
+                module.imports.len(),
+                module.exports.len(),
+                format_size(module.code_size()),
+                format_size(module.data_size())
+            );
+            for import in &module.imports {
+                println!(
+                    "    import {}.{} {}",
+                    import.module,
+                    import.name,
+                    import.ty.dimmed()
+                );
+            }
+        }
+    }
+
+    println!(
+        "\n{} ({})",
+        "Custom sections".bold(),
+        info.custom_sections.len()
+    );
+    for section in &info.custom_sections {
+        println!("  {:<32} {:>10}", section.name, format_size(section.size));
+    }
+
+    match provenance::read(&info) {
+        Some(Ok(provenance)) => {
+            println!("\n{}", "Provenance:".bold());
+            println!("  App: {} {}", provenance.slug, provenance.version);
+            println!(
+                "  Git commit: {}{}",
+                provenance.git_commit.as_deref().unwrap_or("unknown"),
+                if provenance.git_dirty == Some(true) {
+                    " (dirty)".yellow().to_string()
+                } else {
+                    String::new()
+                }
+            );
+            println!("  Profile: {}", provenance.profile);
+            println!("  Source hash: {}", provenance.source_hash);
+            for (tool, version) in &provenance.toolchain {
+                println!("  {}: {}", tool, version);
+            }
+            println!("  Built with klave-cli {}", provenance.cli_version);
+        }
+        Some(Err(error)) => {
+            println!("\n{}", "Provenance:".bold());
+            println!("  {}", error.to_string().red());
+        }
+        None => {}
+    }
+
+    if !info.producers.is_empty() {
+        println!("\n{}", "Producers:".bold());
+        for (field, values) in &info.producers {
+            let values = values
+                .iter()
+                .map(|(name, version)| format!("{} {}", name, version).trim().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("  {}: {}", field, values);
+        }
+    }
+
+    let data_segments: Vec<usize> = info
+        .modules
+        .iter()
+        .flat_map(|m| m.data_segments.iter().copied())
+        .collect();
+    println!(
+        "\n{} ({} segments, {} total)",
+        "Data segments".bold(),
+        data_segments.len(),
+        format_size(data_segments.iter().sum())
+    );
+    for (index, size) in data_segments.iter().enumerate() {
+        println!("  #{:<4} {:>10}", index, format_size(*size));
+    }
+
+    // Per-function code size, largest first
+    let mut functions: Vec<(usize, &wasm::FunctionSize)> = info
+        .modules
+        .iter()
+        .enumerate()
+        .flat_map(|(index, m)| m.functions.iter().map(move |f| (index, f)))
+        .collect();
+    functions.sort_by_key(|(_, f)| std::cmp::Reverse(f.size));
+
+    let total_code: usize = functions.iter().map(|(_, f)| f.size).sum();
+    let shown = if top == 0 {
+        functions.len()
+    } else {
+        top.min(functions.len())
+    };
+
+    println!(
+        "\n{} ({} functions, {} total, showing {})",
+        "Code size by function".bold(),
+        functions.len(),
+        format_size(total_code),
+        shown
+    );
+    for (module_index, function) in functions.iter().take(shown) {
+        let name = match &function.name {
+            Some(name) => name.clone(),
+            None => format!("func[{}]", function.index),
+        };
+        let name = if info.modules.len() > 1 {
+            format!("{} {}", format!("[module {}]", module_index).dimmed(), name)
+        } else {
+            name
+        };
+        let share = if total_code > 0 {
+            function.size as f64 * 100.0 / total_code as f64
+        } else {
+            0.0
+        };
+        println!(
+            "  {:>10} {:>6.2}%  {}",
+            format_size(function.size),
+            share,
+            name
+        );
+    }
+
+    Ok(())
+}
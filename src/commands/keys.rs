@@ -0,0 +1,171 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::util::config;
+use crate::util::keys::{self, Curve, KeyInfo};
+
+fn print_key(key: &KeyInfo) {
+    println!("{}", key.name.bold());
+    println!("  Curve:       {}", key.curve);
+    println!("  Fingerprint: {}", keys::fingerprint(&key.public_key));
+    println!(
+        "  Encrypted:   {}",
+        if key.encrypted {
+            "yes".green()
+        } else {
+            "no".yellow()
+        }
+    );
+    println!("  File:        {}", key.path.display());
+}
+
+/// Generate a new key in the key store
+pub fn generate(name: String, curve: String) -> Result<()> {
+    let curve: Curve = curve.parse()?;
+    let key = keys::generate(&name, curve)?;
+
+    println!("\n{} \"{}\"\n", "Generated key".green(), name);
+    print_key(&key);
+    println!("\nPublic key:\n{}", key.public_key);
+    Ok(())
+}
+
+/// List the keys in the key store
+pub fn list() -> Result<()> {
+    let keys = keys::list()?;
+    if keys.is_empty() {
+        println!(
+            "No keys yet, create one with: {}",
+            "klave keys generate".cyan()
+        );
+        return Ok(());
+    }
+
+    let width = keys.iter().map(|k| k.name.len()).max().unwrap_or(0);
+    for key in keys {
+        println!(
+            "{:<width$}  {:<9}  {}{}",
+            key.name,
+            key.curve.name(),
+            keys::fingerprint(&key.public_key),
+            if key.encrypted {
+                "".normal()
+            } else {
+                "  (unencrypted)".yellow()
+            },
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Show a key and its public key in every supported format
+pub fn show(name: String, format: Option<String>) -> Result<()> {
+    let key = keys::info(&name)?;
+
+    if let Some(format) = format {
+        println!(
+            "{}",
+            keys::format_public_key(key.curve, &key.public_key, &format)?
+        );
+        return Ok(());
+    }
+
+    print_key(&key);
+    for format in keys::PUBLIC_KEY_FORMATS {
+        let value = keys::format_public_key(key.curve, &key.public_key, format)?;
+        println!("\n{} ({}):", "Public key".bold(), format);
+        println!("{}", value.trim_end());
+    }
+    Ok(())
+}
+
+/// Export a public or private key to stdout or a file
+pub fn export(
+    name: String,
+    format: Option<String>,
+    private: bool,
+    output: Option<String>,
+) -> Result<()> {
+    let contents = if private {
+        let format = format.unwrap_or_else(|| "pem".to_string());
+        if !keys::PRIVATE_KEY_FORMATS.contains(&format.as_str()) {
+            return Err(anyhow!(
+                "Unsupported private key format \"{}\", expected one of: {}",
+                format,
+                keys::PRIVATE_KEY_FORMATS.join(", ")
+            ));
+        }
+        keys::load(&name)?.export_private(&format)?
+    } else {
+        let key = keys::info(&name)?;
+        let format = format.unwrap_or_else(|| "base64".to_string());
+        keys::format_public_key(key.curve, &key.public_key, &format)?
+    };
+
+    match output {
+        Some(output) => {
+            let path = Path::new(&output);
+            let contents = format!("{}\n", contents.trim_end());
+            if private {
+                config::write_private_file(path, contents.as_bytes())?;
+            } else {
+                fs::write(path, contents).context(format!("Failed to write {:?}", path))?;
+            }
+            eprintln!("Exported key \"{}\" to {}", name, path.display());
+        }
+        None => {
+            if private {
+                eprintln!(
+                    "{}",
+                    "Warning: Printing an unencrypted private key".yellow()
+                );
+            }
+            println!("{}", contents.trim_end());
+        }
+    }
+    Ok(())
+}
+
+/// Import a private key from a file, or stdin with "-"
+pub fn import(name: String, file: String) -> Result<()> {
+    let contents = if file == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        contents
+    } else {
+        fs::read_to_string(&file).context(format!("Failed to read {:?}", file))?
+    };
+
+    let key = keys::import(&name, &contents)?;
+    println!("\n{} \"{}\"\n", "Imported key".green(), name);
+    print_key(&key);
+    Ok(())
+}
+
+/// Delete a key from the key store
+pub fn delete(name: String, yes: bool) -> Result<()> {
+    let key = keys::info(&name)?;
+
+    if !yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Delete key \"{}\" ({})? This cannot be undone",
+                name,
+                keys::fingerprint(&key.public_key)
+            ))
+            .default(false)
+            .interact()?
+    {
+        println!("Cancelled");
+        return Ok(());
+    }
+
+    keys::delete(&name)?;
+    println!("Deleted key \"{}\"", name);
+    Ok(())
+}
@@ -1,4 +1,14 @@
 // Declare all command modules
+pub mod apps;
+pub mod auth;
 pub mod build;
+pub mod config;
 pub mod create;
+pub mod deploy;
 pub mod info;
+pub mod inspect;
+pub mod keys;
+pub mod pack;
+pub mod reproducible;
+pub mod secrets;
+pub mod verify;
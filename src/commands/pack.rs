@@ -0,0 +1,251 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::util::bundle::{self, Bundle};
+use crate::util::interpolate::Mode;
+use crate::util::wasm::format_size;
+use crate::util::{branches, environment, keys, manifest, project};
+
+/// Default location of the bundle of a project
+fn default_output(cwd: &Path) -> PathBuf {
+    let name = cwd
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bundle".to_string());
+    cwd.join(".klave").join(format!("{}.klave.tar.gz", name))
+}
+
+/// klave.json written to a bundle
+///
+/// It only describes the packed applications, with the environment overlay
+/// and branch overrides applied. Variable references are kept as written so
+/// values from the environment or `.env` stay out of the bundle; `klave
+/// deploy --bundle` expands them.
+fn bundle_config(
+    cwd: &Path,
+    env_name: Option<&str>,
+    applications: &[Value],
+    selected: &[&Value],
+) -> Result<Value> {
+    let (mut config, _) = environment::resolve_with_sources(cwd, env_name, Mode::Mask)?;
+    let branch = branches::current_branch(cwd);
+
+    // Both configurations come from the same layers, so applications line up
+    let packed = applications
+        .iter()
+        .zip(project::applications(&config)?)
+        .filter(|(expanded, _)| selected.iter().any(|app| std::ptr::eq(*app, *expanded)))
+        .map(|(_, application)| match &branch {
+            Some(branch) => branches::apply_overrides(application, branch),
+            None => Ok(application.clone()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(config) = config.as_object_mut() {
+        config.insert("applications".to_string(), Value::Array(packed));
+        config.remove("branches");
+    }
+    Ok(config)
+}
+
+/// Pack built artifacts, the resolved klave.json and a manifest into one archive
+pub fn pack(
+    app: Option<String>,
+    env_name: Option<String>,
+    sources: bool,
+    sign: Option<String>,
+    output: Option<String>,
+) -> Result<()> {
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let klave_config = environment::resolve(&cwd, env_name.as_deref())?;
+    let applications = project::applications(&klave_config)?;
+    let selected = project::select_apps(applications, app.as_deref())?;
+    let resolved = branches::resolve_apps(&cwd, &klave_config, &selected, true)?;
+
+    let staging = tempfile::tempdir()?;
+    let dir = staging.path();
+
+    println!("{} {} application(s)", "Packing".bold(), resolved.len());
+    let mut artifacts = Vec::new();
+    for application in &resolved {
+        let artifact = project::artifact_path(&cwd, application)?;
+        if !artifact.exists() {
+            return Err(anyhow!(
+                "No artifact found for \"{}\" at {:?}. Run 'klave build' first.",
+                project::app_slug(application),
+                artifact
+            ));
+        }
+        let entry = manifest::add_artifact(&cwd, application, dir)?;
+        println!(
+            "  {} {} {} {}",
+            entry.slug.bold(),
+            entry.version,
+            format_size(entry.size),
+            entry.sha256[..12].dimmed()
+        );
+        artifacts.push(entry);
+    }
+
+    let config = bundle_config(&cwd, env_name.as_deref(), applications, &selected)?;
+    fs::write(
+        dir.join(bundle::CONFIG_FILE),
+        serde_json::to_string_pretty(&config)?,
+    )?;
+    let mut files = vec![manifest::add_file(dir, bundle::CONFIG_FILE)?];
+
+    if sources {
+        for application in &resolved {
+            let file = format!(
+                "{}/{}.tar.gz",
+                bundle::SOURCES_DIR,
+                project::app_slug(application)
+            );
+            bundle::write_sources(&project::app_dir(&cwd, application), &dir.join(&file))?;
+            let entry = manifest::add_file(dir, &file)?;
+            println!("  {} {}", file, format_size(entry.size).dimmed());
+            files.push(entry);
+        }
+    }
+
+    let key = match &sign {
+        Some(key_name) => Some(keys::load_or_create(key_name)?),
+        None => None,
+    };
+    manifest::write(dir, artifacts, files, key.as_ref())?;
+
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_output(&cwd));
+    bundle::write_bundle(dir, &output)?;
+
+    println!(
+        "\n{} {} ({})",
+        "Bundle written to".green(),
+        output.display(),
+        format_size(fs::metadata(&output)?.len() as usize)
+    );
+    if let Some(key) = &key {
+        println!(
+            "Manifest signed with \"{}\" (key {})",
+            key.name,
+            keys::fingerprint(&key.public_key()?)
+        );
+    }
+    println!(
+        "Deploy it with: {}",
+        format!("klave deploy --bundle {}", output.display()).cyan()
+    );
+    Ok(())
+}
+
+/// Extract a bundle and check its contents against the manifest
+pub fn unpack(bundle_path: String, output: Option<String>) -> Result<()> {
+    let bundle_path = Path::new(&bundle_path);
+    let output = match output {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let name = bundle_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name = name
+                .strip_suffix(".tar.gz")
+                .or_else(|| name.strip_suffix(".tgz"))
+                .unwrap_or(&name);
+            PathBuf::from(format!("{}.unpacked", name))
+        }
+    };
+
+    if output
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(anyhow!("{:?} already exists and is not empty", output));
+    }
+
+    bundle::extract(bundle_path, &output)?;
+    let bundle = Bundle::check(&output)
+        .map_err(|error| anyhow!("{} (the bundle was extracted to {:?})", error, output))?;
+
+    println!("{} {}\n", "Unpacked to".green(), output.display());
+    for artifact in &bundle.manifest.artifacts {
+        println!(
+            "{} {} {} {}",
+            "✓".green(),
+            artifact.slug.bold(),
+            artifact.version,
+            artifact.sha256.dimmed()
+        );
+    }
+    for file in &bundle.manifest.files {
+        println!(
+            "{} {} {}",
+            "✓".green(),
+            file.file.bold(),
+            file.sha256.dimmed()
+        );
+    }
+
+    match &bundle.manifest.signature {
+        Some(signature) => println!(
+            "\nSigned by \"{}\" (key {}), check it with: {}",
+            signature.key_name,
+            keys::fingerprint(&signature.public_key),
+            format!(
+                "klave verify {} --public-key <KEY>",
+                output.join(bundle::MANIFEST_FILE).display()
+            )
+            .cyan()
+        ),
+        None => println!("\n{}", "The bundle is not signed".yellow()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bundle_config_keeps_variable_references() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("klave.json"),
+            json!({
+                "version": "1",
+                "applications": [
+                    { "slug": "hello", "url": "${KLAVE_T_HOST:-localhost}/hello" },
+                    { "slug": "other", "url": "${KLAVE_T_HOST}/other" }
+                ],
+                "environments": {
+                    "prod": { "applications": [{ "slug": "other", "maxSize": 10 }] }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        fs::write(dir.path().join(".env"), "KLAVE_T_HOST=https://internal\n").unwrap();
+
+        let expanded = environment::resolve(dir.path(), Some("prod")).unwrap();
+        let applications = project::applications(&expanded).unwrap();
+        assert_eq!(applications[1]["url"], "https://internal/other");
+
+        let selected = project::select_apps(applications, Some("other")).unwrap();
+        let config = bundle_config(dir.path(), Some("prod"), applications, &selected).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "version": "1",
+                "applications": [
+                    { "slug": "other", "url": "${KLAVE_T_HOST}/other", "maxSize": 10 }
+                ]
+            })
+        );
+    }
+}
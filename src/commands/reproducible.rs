@@ -0,0 +1,232 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use walkdir::WalkDir;
+
+use crate::commands::build::{build_command, build_profile, post_process};
+use crate::util::provenance::{self, Provenance};
+use crate::util::wasm::{self, format_size};
+use crate::util::{git, project};
+
+/// Directories left out of the isolated copies of the project
+const EXCLUDED_DIRS: [&str; 4] = ["target", ".klave", ".git", "node_modules"];
+
+/// Copy the project sources into a clean directory
+///
+/// Build outputs are left behind so every build starts from scratch. The
+/// installed node_modules are linked rather than copied.
+fn copy_project(cwd: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(cwd)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && EXCLUDED_DIRS.contains(&e.file_name().to_string_lossy().as_ref()))
+        })
+    {
+        let entry = entry?;
+        let target = dest.join(entry.path().strip_prefix(cwd)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_file() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    let node_modules = cwd.join("node_modules");
+    if node_modules.exists() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&node_modules, dest.join("node_modules"))?;
+        #[cfg(not(unix))]
+        fs_extra::dir::copy(&node_modules, dest, &fs_extra::dir::CopyOptions::new())?;
+    }
+
+    Ok(())
+}
+
+/// Build an application in an isolated copy of the project and return the artifact
+///
+/// The artifact goes through the same post-processing as `klave build`, so
+/// what is compared is what would be deployed.
+fn isolated_build(
+    cwd: &Path,
+    application: &Value,
+    package_manager: &str,
+    source_date_epoch: &str,
+    optimize: Option<&str>,
+    provenance: &Provenance,
+) -> Result<Vec<u8>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    copy_project(cwd, root)?;
+
+    let app_dir = project::app_dir(root, application);
+    let app_type = project::app_type(&app_dir);
+    let app_slug = project::app_slug(application);
+    let (command, args, build_dir) =
+        build_command(app_type, package_manager, app_slug, root, &app_dir);
+
+    // Normalise the environment and keep the temporary path out of the output.
+    // CARGO_ENCODED_RUSTFLAGS takes precedence over RUSTFLAGS and keeps paths
+    // with spaces in one flag.
+    let mut rustflags: Vec<String> = match env::var("CARGO_ENCODED_RUSTFLAGS") {
+        Ok(flags) => flags
+            .split('\x1f')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => env::var("RUSTFLAGS")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    };
+    rustflags.push(format!("--remap-path-prefix={}=/klave", root.display()));
+
+    let status = Command::new(command)
+        .args(&args)
+        .current_dir(&build_dir)
+        .env("SOURCE_DATE_EPOCH", source_date_epoch)
+        .env("TZ", "UTC")
+        .env("LC_ALL", "C")
+        .env("LANG", "C")
+        .env("CARGO_INCREMENTAL", "0")
+        .env("CARGO_ENCODED_RUSTFLAGS", rustflags.join("\x1f"))
+        .env_remove("RUSTFLAGS")
+        .env_remove("CARGO_TARGET_DIR")
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .status()
+        .context(format!("Failed to execute command: {} {:?}", command, args))?;
+
+    if !status.success() {
+        return Err(anyhow!("Build failed in {:?}", root));
+    }
+
+    post_process(root, application, optimize, provenance)?;
+    let artifact = project::artifact_path(root, application)?;
+    fs::read(&artifact).context(format!("No artifact found at {:?}", artifact))
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Describe the sections that differ between two builds
+fn differing_sections(first: &[u8], second: &[u8]) -> Result<Vec<String>> {
+    let first: BTreeMap<String, &[u8]> = wasm::section_labels(first)?.into_iter().collect();
+    let second: BTreeMap<String, &[u8]> = wasm::section_labels(second)?.into_iter().collect();
+
+    let mut labels: Vec<&String> = first.keys().chain(second.keys()).collect();
+    labels.sort();
+    labels.dedup();
+
+    Ok(labels
+        .into_iter()
+        .filter_map(|label| match (first.get(label), second.get(label)) {
+            (Some(a), Some(b)) if a == b => None,
+            (Some(a), Some(b)) => Some(format!(
+                "{} ({} vs {})",
+                label,
+                format_size(a.len()),
+                format_size(b.len())
+            )),
+            (Some(_), None) => Some(format!("{} (only in build 1)", label)),
+            (None, _) => Some(format!("{} (only in build 2)", label)),
+        })
+        .collect())
+}
+
+/// Build each application twice in clean directories and compare the outputs
+pub fn execute(
+    cwd: &Path,
+    applications: &[&Value],
+    package_manager: &str,
+    optimize: Option<&str>,
+) -> Result<()> {
+    let source_date_epoch = git::commit_timestamp(cwd).unwrap_or_else(|| "0".to_string());
+    let mut non_reproducible = Vec::new();
+
+    for application in applications {
+        let app_slug = project::app_slug(application);
+        println!(
+            "\n{} \"{}\" (SOURCE_DATE_EPOCH={})",
+            "Verifying reproducibility of".bold(),
+            app_slug,
+            source_date_epoch
+        );
+
+        // Both builds carry the provenance of the project, as `klave build` would
+        let app_dir = project::app_dir(cwd, application);
+        let app_type = project::app_type(&app_dir);
+        let (_, args, _) = build_command(app_type, package_manager, app_slug, cwd, &app_dir);
+        let profile = build_profile(app_type, &args, cwd);
+        let builds = provenance::collect(cwd, application, profile).and_then(|provenance| {
+            (1..=2)
+                .map(|n| {
+                    println!("  Build {}...", n);
+                    isolated_build(
+                        cwd,
+                        application,
+                        package_manager,
+                        &source_date_epoch,
+                        optimize,
+                        &provenance,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+
+        let builds = match builds {
+            Ok(builds) => builds,
+            Err(error) => {
+                eprintln!("  {}", format!("Error: {}", error).red());
+                non_reproducible.push(app_slug);
+                continue;
+            }
+        };
+
+        let (first, second) = (&builds[0], &builds[1]);
+        println!("  Build 1: sha256 {}", sha256(first));
+        println!("  Build 2: sha256 {}", sha256(second));
+
+        if first == second {
+            println!("  {}", "✓ Reproducible".green());
+            continue;
+        }
+
+        println!("  {}", "✗ Not reproducible".red());
+        non_reproducible.push(app_slug);
+        match differing_sections(first, second) {
+            Ok(sections) => {
+                println!("  Differing sections:");
+                for section in sections {
+                    println!("    - {}", section);
+                }
+            }
+            Err(error) => eprintln!("  Could not compare sections: {}", error),
+        }
+    }
+
+    println!(
+        "\n{}: {}/{} apps",
+        "Reproducible builds".bold(),
+        applications.len() - non_reproducible.len(),
+        applications.len()
+    );
+
+    if !non_reproducible.is_empty() {
+        return Err(anyhow!(
+            "Non-reproducible applications: {}",
+            non_reproducible.join(", ")
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use dialoguer::Password;
+use dialoguer::theme::ColorfulTheme;
+use std::env;
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+
+use crate::util::{environment, project, secrets};
+
+/// Project directory, after checking the app is one of its applications
+fn project_app(app: &str) -> Result<(PathBuf, String)> {
+    let cwd = env::current_dir().context("Failed to get current directory")?;
+    let klave_config = environment::resolve(&cwd, None)?;
+    let applications = project::applications(&klave_config)?;
+    let slug = project::app_slug(project::select_apps(applications, Some(app))?[0]).to_string();
+    Ok((cwd, slug))
+}
+
+/// Value of a secret given without `=`, from a hidden prompt or stdin
+fn read_value(name: &str) -> Result<String> {
+    if io::stdin().is_terminal() {
+        return Password::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Value of {}", name))
+            .allow_empty_password(true)
+            .interact()
+            .context("Failed to read the secret value");
+    }
+
+    let mut value = String::new();
+    io::stdin().read_to_string(&mut value)?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Set secrets of an app from `NAME=value` assignments, or prompt for `NAME`
+pub fn set(app: String, assignments: Vec<String>) -> Result<()> {
+    let (cwd, slug) = project_app(&app)?;
+
+    let prompted = assignments.iter().filter(|a| !a.contains('=')).count();
+    if prompted > 1 && !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Only one secret can be read from stdin, give the others as NAME=value"
+        ));
+    }
+
+    let mut values = Vec::new();
+    for assignment in &assignments {
+        let (name, value) = match assignment.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (assignment.clone(), read_value(assignment)?),
+        };
+        secrets::check_name(&name)?;
+        values.push((name, value));
+    }
+
+    let mut all = secrets::load(&cwd)?;
+    for (name, value) in values {
+        let verb = if all.set(&slug, &name, value) {
+            "Updated"
+        } else {
+            "Set"
+        };
+        println!("{} {} for \"{}\"", verb.green(), name.bold(), slug);
+    }
+    secrets::save(&cwd, &all)?;
+
+    println!("\nSecrets are pushed on the next {}", "klave deploy".cyan());
+    Ok(())
+}
+
+/// Remove secrets of an app
+pub fn unset(app: String, names: Vec<String>) -> Result<()> {
+    let (cwd, slug) = project_app(&app)?;
+    let mut all = secrets::load(&cwd)?;
+
+    let mut removed = 0;
+    for name in &names {
+        if all.unset(&slug, name) {
+            println!("{} {} from \"{}\"", "Removed".green(), name.bold(), slug);
+            removed += 1;
+        } else {
+            eprintln!(
+                "{}",
+                format!("Warning: No secret {} for \"{}\"", name, slug).yellow()
+            );
+        }
+    }
+
+    if removed > 0 {
+        secrets::save(&cwd, &all)?;
+        println!(
+            "\nRemoved secrets are deleted from the platform on the next {}",
+            "klave deploy".cyan()
+        );
+    }
+    Ok(())
+}
+
+/// List the names of the secrets of an app, never their values
+pub fn list(app: String, json: bool) -> Result<()> {
+    let (cwd, slug) = project_app(&app)?;
+    let names: Vec<String> = secrets::for_app(&cwd, &slug)?.into_keys().collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&names)?);
+        return Ok(());
+    }
+
+    if names.is_empty() {
+        println!(
+            "No secrets for \"{}\", add one with: {}",
+            slug,
+            format!("klave secrets set {} NAME", slug).cyan()
+        );
+        return Ok(());
+    }
+
+    println!("{} ({} secrets)", slug.bold(), names.len());
+    for name in names {
+        println!("  {}", name);
+    }
+    Ok(())
+}
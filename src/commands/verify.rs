@@ -0,0 +1,81 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::path::Path;
+
+use crate::util::keys;
+use crate::util::manifest::{self, Trust};
+
+/// Main verify command implementation
+pub fn execute(manifest_path: String, public_key: Option<String>) -> Result<()> {
+    let manifest_path = Path::new(&manifest_path);
+    let manifest = manifest::read(manifest_path)?;
+    let dir = manifest_path.parent().unwrap_or(Path::new("."));
+
+    println!("\n{} {}", "Verifying".bold(), manifest_path.display());
+
+    let (signature, trust) = manifest::verify_trusted(&manifest, public_key.as_deref())?;
+    println!(
+        "{} signed by \"{}\" (key {})",
+        "✓ Signature valid:".green(),
+        signature.key_name,
+        keys::fingerprint(&signature.public_key)
+    );
+    match trust {
+        Trust::Expected => println!("  Matches the expected public key"),
+        Trust::LocalKey(name) => println!("  Matches your local key \"{}\"", name),
+    }
+
+    let mut failures = 0;
+    for artifact in &manifest.artifacts {
+        match manifest::check_file(dir, &artifact.file, &artifact.sha256) {
+            Ok(()) => println!(
+                "{} {} {} {}",
+                "✓".green(),
+                artifact.slug.bold(),
+                artifact.version,
+                artifact.sha256.dimmed()
+            ),
+            Err(error) => {
+                failures += 1;
+                println!(
+                    "{} {} {} {}",
+                    "✗".red(),
+                    artifact.slug.bold(),
+                    artifact.version,
+                    error.red()
+                );
+            }
+        }
+    }
+
+    for file in &manifest.files {
+        match manifest::check_file(dir, &file.file, &file.sha256) {
+            Ok(()) => println!(
+                "{} {} {}",
+                "✓".green(),
+                file.file.bold(),
+                file.sha256.dimmed()
+            ),
+            Err(error) => {
+                failures += 1;
+                println!("{} {} {}", "✗".red(), file.file.bold(), error.red());
+            }
+        }
+    }
+
+    let total = manifest.artifacts.len() + manifest.files.len();
+    if failures > 0 {
+        return Err(anyhow!(
+            "{} of {} files do not match the manifest",
+            failures,
+            total
+        ));
+    }
+
+    if manifest.files.is_empty() {
+        println!("\nAll {} artifacts match the manifest", total);
+    } else {
+        println!("\nAll {} files match the manifest", total);
+    }
+    Ok(())
+}
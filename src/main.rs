@@ -49,6 +49,349 @@ enum Commands {
         /// Output verbose build information
         #[clap(short, long)]
         verbose: bool,
+
+        /// Optimize artifacts with wasm-opt (levels: O0-O4, Os, Oz; defaults to Os)
+        #[clap(long, num_args = 0..=1, default_missing_value = "s", value_name = "LEVEL")]
+        optimize: Option<String>,
+
+        /// Build each app twice in clean directories and compare the outputs
+        #[clap(long, conflicts_with = "sign")]
+        verify_reproducible: bool,
+
+        /// Write a manifest of the artifacts to .klave/dist, signed with a key from `klave keys`
+        #[clap(long, num_args = 0..=1, default_missing_value = "default", value_name = "KEY")]
+        sign: Option<String>,
+
+        /// Apply per-branch overrides from klave.json and warn if the branch isn't deployable
+        #[clap(long)]
+        branch_aware: bool,
+
+        /// Environment overlay to apply (klave.<env>.json or environments.<env>), defaults to KLAVE_ENV
+        #[clap(long)]
+        env: Option<String>,
+    },
+
+    /// Deploy built applications to Klave
+    Deploy {
+        /// Specific application to deploy (deploys all if not specified)
+        #[clap(short, long)]
+        app: Option<String>,
+
+        /// Environment overlay to apply (klave.<env>.json or environments.<env>), defaults to KLAVE_ENV
+        #[clap(long, conflicts_with = "bundle")]
+        env: Option<String>,
+
+        /// Deploy the artifacts and klave.json of a bundle from `klave pack`
+        #[clap(long, value_name = "FILE")]
+        bundle: Option<String>,
+
+        /// Base64 public key the bundle must be signed with (defaults to your local keys)
+        #[clap(long, requires = "bundle")]
+        public_key: Option<String>,
+
+        /// Deploy a bundle even if it is not signed
+        #[clap(long, requires = "bundle", conflicts_with = "public_key")]
+        allow_unsigned: bool,
+
+        /// Base URL of the Klave API (defaults to KLAVE_API_URL or https://api.klave.com)
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Pack built artifacts, the resolved klave.json and a manifest into a bundle
+    Pack {
+        /// Specific application to pack (packs all if not specified)
+        #[clap(short, long)]
+        app: Option<String>,
+
+        /// Environment overlay to apply (klave.<env>.json or environments.<env>), defaults to KLAVE_ENV
+        #[clap(long)]
+        env: Option<String>,
+
+        /// Include a tarball of each application's sources
+        #[clap(long)]
+        sources: bool,
+
+        /// Sign the bundle manifest with a key from `klave keys`
+        #[clap(long, num_args = 0..=1, default_missing_value = "default", value_name = "KEY")]
+        sign: Option<String>,
+
+        /// Output file (defaults to .klave/<project>.klave.tar.gz)
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+
+    /// Extract a bundle and check it against its manifest
+    Unpack {
+        /// Bundle file from `klave pack`
+        #[clap(value_parser)]
+        bundle: String,
+
+        /// Directory to extract to (defaults to <bundle>.unpacked)
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+
+    /// Show the state of deployed applications
+    Status {
+        /// Application slug (all applications in klave.json if not specified)
+        #[clap(value_parser)]
+        app: Option<String>,
+
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Base URL of the Klave API
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Show the logs of an application
+    Logs {
+        /// Application slug (required when klave.json lists several apps)
+        #[clap(value_parser)]
+        app: Option<String>,
+
+        /// Keep waiting for new log entries
+        #[clap(short, long)]
+        follow: bool,
+
+        /// Print one JSON object per entry
+        #[clap(long)]
+        json: bool,
+
+        /// Base URL of the Klave API
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Inspect the project configuration
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Manage deployments
+    Deployments {
+        #[clap(subcommand)]
+        command: DeploymentsCommands,
+    },
+
+    /// Serve a previous deployment of an application again
+    Rollback {
+        /// Application slug
+        #[clap(value_parser)]
+        app: String,
+
+        /// Deployment to roll back to
+        #[clap(value_parser)]
+        deployment: String,
+
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Base URL of the Klave API
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Log in to Klave
+    Login {
+        /// Read an API token from stdin instead of logging in through the browser
+        #[clap(long)]
+        with_token: bool,
+
+        /// Base URL of the Klave API (defaults to KLAVE_API_URL or https://api.klave.com)
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Remove the stored credentials
+    Logout,
+
+    /// Show the account you are logged in as
+    Whoami {
+        /// Base URL of the Klave API
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+
+    /// Inspect a built wasm artifact
+    Inspect {
+        /// Application slug or path to a .wasm file
+        #[clap(value_parser)]
+        target: String,
+
+        /// Number of functions to show in the code size table (0 shows all)
+        #[clap(long, default_value_t = 20)]
+        top: usize,
+    },
+
+    /// Verify the signature and artifact hashes of a build manifest
+    Verify {
+        /// Path to the manifest.json file
+        #[clap(value_parser)]
+        manifest: String,
+
+        /// Base64 public key the manifest must be signed with (defaults to your local keys)
+        #[clap(long)]
+        public_key: Option<String>,
+    },
+
+    /// Manage developer keys
+    Keys {
+        #[clap(subcommand)]
+        command: KeysCommands,
+    },
+
+    /// Manage the secrets of an application
+    ///
+    /// Values are encrypted in .klave/secrets.json with a key of the current
+    /// user, kept in secrets.key in the klave config directory (or
+    /// KLAVE_CONFIG_DIR) and created on first use. Other users and machines
+    /// cannot read the file without that key: to share the secrets with a
+    /// teammate or CI, give them the contents of secrets.key as
+    /// KLAVE_SECRETS_KEY.
+    ///
+    /// Secrets are pushed on deploy for apps that have some; secrets removed
+    /// with unset are removed from the platform on the next deploy.
+    Secrets {
+        #[clap(subcommand)]
+        command: SecretsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the resolved klave.json and the source of each value
+    Show {
+        /// Environment overlay to apply, defaults to KLAVE_ENV
+        #[clap(long)]
+        env: Option<String>,
+
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeploymentsCommands {
+    /// List the deployments of applications
+    List {
+        /// Application slug (all applications in klave.json if not specified)
+        #[clap(value_parser)]
+        app: Option<String>,
+
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Base URL of the Klave API
+        #[clap(long)]
+        api_url: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsCommands {
+    /// Set secrets, as NAME=value or NAME to enter the value without echo
+    Set {
+        /// Application slug
+        app: String,
+
+        /// Secrets to set
+        #[clap(required = true, value_name = "NAME[=VALUE]")]
+        secrets: Vec<String>,
+    },
+    /// Remove secrets
+    Unset {
+        /// Application slug
+        app: String,
+
+        /// Names of the secrets to remove
+        #[clap(required = true)]
+        names: Vec<String>,
+    },
+    /// List the names of the secrets of an application
+    List {
+        /// Application slug
+        app: String,
+
+        /// Print JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// Generate a new key
+    Generate {
+        /// Name of the key
+        #[clap(value_parser, default_value = "default")]
+        name: String,
+
+        /// Curve of the key (secp256r1, secp384r1 or secp256k1)
+        #[clap(long, default_value = "secp256r1")]
+        curve: String,
+    },
+
+    /// List the keys in the key store
+    List,
+
+    /// Show a key and its public key
+    Show {
+        /// Name of the key
+        #[clap(value_parser, default_value = "default")]
+        name: String,
+
+        /// Only print the public key in this format (base64, pem, jwk or hex)
+        #[clap(long)]
+        format: Option<String>,
+    },
+
+    /// Export a public key, or the private key with --private
+    Export {
+        /// Name of the key
+        #[clap(value_parser, default_value = "default")]
+        name: String,
+
+        /// Output format (public: base64, pem, jwk, hex; private: pem, jwk)
+        #[clap(long)]
+        format: Option<String>,
+
+        /// Export the decrypted private key
+        #[clap(long)]
+        private: bool,
+
+        /// Write to a file instead of stdout
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+
+    /// Import a private key (PKCS#8 PEM, base64 DER or JWK)
+    Import {
+        /// Name of the key
+        #[clap(value_parser)]
+        name: String,
+
+        /// File to read the key from, "-" for stdin
+        #[clap(value_parser)]
+        file: String,
+    },
+
+    /// Delete a key
+    Delete {
+        /// Name of the key
+        #[clap(value_parser)]
+        name: String,
+
+        /// Do not ask for confirmation
+        #[clap(short, long)]
+        yes: bool,
     },
 }
 
@@ -75,6 +418,11 @@ fn run() -> Result<(), Box<dyn Error>> {
             app,
             skip_checks,
             verbose,
+            optimize,
+            verify_reproducible,
+            sign,
+            branch_aware,
+            env,
         } => {
             // Create a tokio runtime for the async execute function
             let rt = tokio::runtime::Runtime::new()?;
@@ -82,8 +430,124 @@ fn run() -> Result<(), Box<dyn Error>> {
                 app.clone(),
                 *skip_checks,
                 *verbose,
+                optimize.clone(),
+                *verify_reproducible,
+                sign.clone(),
+                *branch_aware,
+                env.clone(),
             ))?;
         }
+        Commands::Deploy {
+            app,
+            env,
+            bundle,
+            public_key,
+            allow_unsigned,
+            api_url,
+        } => {
+            commands::deploy::execute(
+                app.clone(),
+                env.clone(),
+                bundle.clone(),
+                public_key.clone(),
+                *allow_unsigned,
+                api_url.clone(),
+            )?;
+        }
+        Commands::Pack {
+            app,
+            env,
+            sources,
+            sign,
+            output,
+        } => {
+            commands::pack::pack(
+                app.clone(),
+                env.clone(),
+                *sources,
+                sign.clone(),
+                output.clone(),
+            )?;
+        }
+        Commands::Unpack { bundle, output } => {
+            commands::pack::unpack(bundle.clone(), output.clone())?;
+        }
+        Commands::Status { app, json, api_url } => {
+            commands::apps::status(app.clone(), api_url.clone(), *json)?;
+        }
+        Commands::Logs {
+            app,
+            follow,
+            json,
+            api_url,
+        } => {
+            commands::apps::logs(app.clone(), api_url.clone(), *follow, *json)?;
+        }
+        Commands::Config { command } => match command {
+            ConfigCommands::Show { env, json } => commands::config::show(env.clone(), *json)?,
+        },
+        Commands::Deployments { command } => match command {
+            DeploymentsCommands::List { app, json, api_url } => {
+                commands::apps::list_deployments(app.clone(), api_url.clone(), *json)?;
+            }
+        },
+        Commands::Rollback {
+            app,
+            deployment,
+            json,
+            api_url,
+        } => {
+            commands::apps::rollback(app.clone(), deployment.clone(), api_url.clone(), *json)?;
+        }
+        Commands::Login {
+            with_token,
+            api_url,
+        } => {
+            commands::auth::login(api_url.clone(), *with_token)?;
+        }
+        Commands::Logout => commands::auth::logout()?,
+        Commands::Whoami { api_url } => commands::auth::whoami(api_url.clone())?,
+        Commands::Inspect { target, top } => {
+            commands::inspect::execute(target.clone(), *top)?;
+        }
+        Commands::Verify {
+            manifest,
+            public_key,
+        } => {
+            commands::verify::execute(manifest.clone(), public_key.clone())?;
+        }
+        Commands::Keys { command } => match command {
+            KeysCommands::Generate { name, curve } => {
+                commands::keys::generate(name.clone(), curve.clone())?;
+            }
+            KeysCommands::List => commands::keys::list()?,
+            KeysCommands::Show { name, format } => {
+                commands::keys::show(name.clone(), format.clone())?;
+            }
+            KeysCommands::Export {
+                name,
+                format,
+                private,
+                output,
+            } => {
+                commands::keys::export(name.clone(), format.clone(), *private, output.clone())?;
+            }
+            KeysCommands::Import { name, file } => {
+                commands::keys::import(name.clone(), file.clone())?;
+            }
+            KeysCommands::Delete { name, yes } => {
+                commands::keys::delete(name.clone(), *yes)?;
+            }
+        },
+        Commands::Secrets { command } => match command {
+            SecretsCommands::Set { app, secrets } => {
+                commands::secrets::set(app.clone(), secrets.clone())?;
+            }
+            SecretsCommands::Unset { app, names } => {
+                commands::secrets::unset(app.clone(), names.clone())?;
+            }
+            SecretsCommands::List { app, json } => commands::secrets::list(app.clone(), *json)?,
+        },
     }
 
     Ok(())
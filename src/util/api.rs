@@ -0,0 +1,759 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Read;
+use std::time::Duration;
+
+use crate::util::credentials::{self, Account};
+use crate::util::manifest;
+
+/// Klave API used when no other base URL is configured
+pub const DEFAULT_API_URL: &str = "https://api.klave.com";
+
+/// Base URL of the Klave API
+///
+/// Taken from the command line, `KLAVE_API_URL`, the API logged in to, or
+/// the default, in that order.
+pub fn base_url(api_url: Option<&str>) -> String {
+    api_url
+        .map(|url| url.to_string())
+        .or_else(|| env::var("KLAVE_API_URL").ok())
+        .or_else(|| {
+            credentials::load()
+                .ok()
+                .flatten()
+                .map(|credentials| credentials.api_url)
+        })
+        .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Deployment of an application version
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub id: String,
+    pub app: String,
+    #[serde(default)]
+    pub version: String,
+    pub status: String,
+    /// SHA-256 of the deployed artifact
+    #[serde(default)]
+    pub artifact: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// Current state of an application
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatus {
+    pub app: String,
+    pub status: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Deployment currently serving the application
+    #[serde(default)]
+    pub deployment: Option<Deployment>,
+}
+
+/// Log line emitted by an application
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: String,
+    #[serde(default)]
+    pub level: String,
+    pub message: String,
+    #[serde(default)]
+    pub deployment: Option<String>,
+}
+
+/// Page of logs, with the cursor to fetch the following entries
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Request to deploy an uploaded artifact
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentRequest {
+    pub version: String,
+    /// SHA-256 of the uploaded artifact
+    pub artifact: String,
+    /// The application's entry in klave.json
+    pub config: Value,
+    pub provenance: Option<Value>,
+}
+
+/// Device code issued to start a browser login
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Verification link with the user code filled in
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    /// Seconds to wait between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    pub expires_in: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// State of a pending device login
+pub enum DeviceLogin {
+    Pending,
+    SlowDown,
+    Complete(String),
+}
+
+/// Authentication operations of the Klave API
+pub trait AuthApi {
+    /// Start a device login, to be approved in the browser
+    fn start_device_login(&self) -> Result<DeviceCode>;
+
+    /// Check whether a device login was approved, returning its token
+    fn poll_device_login(&self, device_code: &str) -> Result<DeviceLogin>;
+
+    /// Account the client's token belongs to
+    fn whoami(&self) -> Result<Account>;
+}
+
+/// Operations of the Klave deployment API
+///
+/// Artifacts are addressed by their SHA-256 so uploading the same build twice
+/// is a no-op, and deployments are created with a hash of the whole request
+/// (artifact, version, configuration and provenance) as their idempotency
+/// key, so only a retry of the same deployment is deduplicated.
+pub trait DeploymentApi {
+    /// Whether the artifact with this hash was already uploaded for an app
+    fn has_artifact(&self, app: &str, sha256: &str) -> Result<bool>;
+
+    /// Upload an artifact of `size` bytes
+    fn upload_artifact(
+        &self,
+        app: &str,
+        sha256: &str,
+        artifact: &mut dyn Read,
+        size: u64,
+    ) -> Result<()>;
+
+    /// Update the secrets of an app, applied to its next deployment
+    ///
+    /// Secrets not given are kept and `None` removes one, as in a JSON merge
+    /// patch.
+    fn set_secrets(&self, app: &str, secrets: &BTreeMap<String, Option<String>>) -> Result<()>;
+
+    /// Deploy an uploaded artifact
+    fn create_deployment(&self, app: &str, request: &DeploymentRequest) -> Result<Deployment>;
+
+    /// Current state of an app
+    fn app_status(&self, app: &str) -> Result<AppStatus>;
+
+    /// Deployments of an app, most recent first
+    fn list_deployments(&self, app: &str) -> Result<Vec<Deployment>>;
+
+    /// Logs of an app, after `cursor` when given
+    fn logs(&self, app: &str, cursor: Option<&str>) -> Result<LogPage>;
+
+    /// Serve a previous deployment again
+    fn rollback(&self, app: &str, deployment: &str) -> Result<Deployment>;
+}
+
+/// Client for the Klave HTTP API
+///
+/// Endpoints, relative to the base URL:
+/// - `POST /v1/auth/device`
+/// - `POST /v1/auth/device/token`
+/// - `GET  /v1/me`
+/// - `HEAD /v1/apps/{app}/artifacts/{sha256}`
+/// - `PUT  /v1/apps/{app}/artifacts/{sha256}`
+/// - `PATCH /v1/apps/{app}/secrets`
+/// - `POST /v1/apps/{app}/deployments`
+/// - `GET  /v1/apps/{app}`
+/// - `GET  /v1/apps/{app}/deployments`
+/// - `GET  /v1/apps/{app}/logs?cursor={cursor}`
+/// - `POST /v1/apps/{app}/deployments/{id}/rollback`
+pub struct HttpClient {
+    base_url: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl HttpClient {
+    pub fn new(base_url: String, token: Option<String>) -> Self {
+        HttpClient {
+            base_url,
+            token,
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(10))
+                .user_agent(&format!("klave-cli/{}", env!("CARGO_PKG_VERSION")))
+                .build(),
+        }
+    }
+
+    /// Client for the configured API, authenticated with the current token if any
+    pub fn configured(api_url: Option<&str>) -> Result<Self> {
+        let base_url = base_url(api_url);
+        let token = credentials::token(&base_url)?.map(|(token, _)| token);
+        Ok(HttpClient::new(base_url, token))
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self
+            .agent
+            .request(method, &format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+}
+
+/// Percent-encode a value used as a path segment, e.g. an app slug
+fn segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Idempotency key of a deployment request
+///
+/// Going through Value sorts the keys, so the same request gives the same key.
+fn idempotency_key(request: &DeploymentRequest) -> Result<String> {
+    Ok(manifest::sha256(&serde_json::to_vec(
+        &serde_json::to_value(request)?,
+    )?))
+}
+
+/// Turn an API error into a readable message, including the server's explanation
+fn api_error(error: ureq::Error) -> anyhow::Error {
+    match error {
+        ureq::Error::Status(401, _) => {
+            anyhow!("Not authenticated with the Klave API, run 'klave login' or set KLAVE_TOKEN")
+        }
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|v| v.get("message")?.as_str().map(|m| m.to_string()))
+                .unwrap_or(body);
+            anyhow!("Klave API request failed ({}): {}", code, message.trim())
+        }
+        ureq::Error::Transport(transport) => {
+            anyhow!("Could not reach the Klave API: {}", transport)
+        }
+    }
+}
+
+/// Parse a JSON response body
+fn json<T: serde::de::DeserializeOwned>(response: ureq::Response) -> Result<T> {
+    response
+        .into_json()
+        .map_err(|e| anyhow!("Invalid response from the Klave API: {}", e))
+}
+
+impl AuthApi for HttpClient {
+    fn start_device_login(&self) -> Result<DeviceCode> {
+        json(
+            self.request("POST", "/v1/auth/device")
+                .send_json(serde_json::json!({ "client": "klave-cli" }))
+                .map_err(api_error)?,
+        )
+    }
+
+    fn poll_device_login(&self, device_code: &str) -> Result<DeviceLogin> {
+        match self
+            .request("POST", "/v1/auth/device/token")
+            .send_json(serde_json::json!({ "deviceCode": device_code }))
+        {
+            Ok(response) => {
+                let body: Value = json(response)?;
+                body.get("token")
+                    .and_then(|t| t.as_str())
+                    .map(|token| DeviceLogin::Complete(token.to_string()))
+                    .ok_or_else(|| anyhow!("Invalid response from the Klave API: missing token"))
+            }
+            Err(ureq::Error::Status(400, response)) => {
+                let body: Value = response.into_json().unwrap_or_default();
+                match body.get("error").and_then(|e| e.as_str()) {
+                    Some("authorization_pending") => Ok(DeviceLogin::Pending),
+                    Some("slow_down") => Ok(DeviceLogin::SlowDown),
+                    Some("expired_token") => Err(anyhow!("The login request expired, try again")),
+                    Some("access_denied") => Err(anyhow!("The login request was denied")),
+                    _ => Err(anyhow!("Login failed: {}", body)),
+                }
+            }
+            Err(error) => Err(api_error(error)),
+        }
+    }
+
+    fn whoami(&self) -> Result<Account> {
+        json(self.request("GET", "/v1/me").call().map_err(api_error)?)
+    }
+}
+
+impl DeploymentApi for HttpClient {
+    fn has_artifact(&self, app: &str, sha256: &str) -> Result<bool> {
+        match self
+            .request(
+                "HEAD",
+                &format!("/v1/apps/{}/artifacts/{}", segment(app), segment(sha256)),
+            )
+            .call()
+        {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::Status(404, _)) => Ok(false),
+            Err(error) => Err(api_error(error)),
+        }
+    }
+
+    fn upload_artifact(
+        &self,
+        app: &str,
+        sha256: &str,
+        artifact: &mut dyn Read,
+        size: u64,
+    ) -> Result<()> {
+        self.request(
+            "PUT",
+            &format!("/v1/apps/{}/artifacts/{}", segment(app), segment(sha256)),
+        )
+        .set("Content-Type", "application/wasm")
+        .set("Content-Length", &size.to_string())
+        .send(artifact)
+        .map_err(api_error)?;
+        Ok(())
+    }
+
+    fn set_secrets(&self, app: &str, secrets: &BTreeMap<String, Option<String>>) -> Result<()> {
+        self.request("PATCH", &format!("/v1/apps/{}/secrets", segment(app)))
+            .set("Content-Type", "application/merge-patch+json")
+            .send_json(secrets)
+            .map_err(api_error)?;
+        Ok(())
+    }
+
+    fn create_deployment(&self, app: &str, request: &DeploymentRequest) -> Result<Deployment> {
+        json(
+            self.request("POST", &format!("/v1/apps/{}/deployments", segment(app)))
+                .set("Idempotency-Key", &idempotency_key(request)?)
+                .send_json(request)
+                .map_err(api_error)?,
+        )
+    }
+
+    fn app_status(&self, app: &str) -> Result<AppStatus> {
+        json(
+            self.request("GET", &format!("/v1/apps/{}", segment(app)))
+                .call()
+                .map_err(api_error)?,
+        )
+    }
+
+    fn list_deployments(&self, app: &str) -> Result<Vec<Deployment>> {
+        json(
+            self.request("GET", &format!("/v1/apps/{}/deployments", segment(app)))
+                .call()
+                .map_err(api_error)?,
+        )
+    }
+
+    fn logs(&self, app: &str, cursor: Option<&str>) -> Result<LogPage> {
+        let mut request = self.request("GET", &format!("/v1/apps/{}/logs", segment(app)));
+        if let Some(cursor) = cursor {
+            request = request.query("cursor", cursor);
+        }
+        json(request.call().map_err(api_error)?)
+    }
+
+    fn rollback(&self, app: &str, deployment: &str) -> Result<Deployment> {
+        json(
+            self.request(
+                "POST",
+                &format!(
+                    "/v1/apps/{}/deployments/{}/rollback",
+                    segment(app),
+                    segment(deployment)
+                ),
+            )
+            .call()
+            .map_err(api_error)?,
+        )
+    }
+}
+
+/// In-memory stand-in for the Klave API, for tests
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    pub struct MockApi {
+        /// Uploaded artifacts by app and hash
+        pub artifacts: RefCell<BTreeMap<(String, String), Vec<u8>>>,
+        pub secrets: RefCell<BTreeMap<String, BTreeMap<String, String>>>,
+        /// Deployments of every app, oldest first
+        pub deployments: RefCell<Vec<Deployment>>,
+        /// Deployment serving each app
+        pub serving: RefCell<BTreeMap<String, String>>,
+        pub logs: RefCell<Vec<LogEntry>>,
+    }
+
+    impl MockApi {
+        fn deployment(&self, app: &str, id: &str) -> Result<Deployment> {
+            self.deployments
+                .borrow()
+                .iter()
+                .find(|d| d.app == app && d.id == id)
+                .cloned()
+                .ok_or_else(|| anyhow!("Klave API request failed (404): deployment not found"))
+        }
+    }
+
+    impl DeploymentApi for MockApi {
+        fn has_artifact(&self, app: &str, sha256: &str) -> Result<bool> {
+            Ok(self
+                .artifacts
+                .borrow()
+                .contains_key(&(app.to_string(), sha256.to_string())))
+        }
+
+        fn upload_artifact(
+            &self,
+            app: &str,
+            sha256: &str,
+            artifact: &mut dyn Read,
+            size: u64,
+        ) -> Result<()> {
+            let mut bytes = Vec::new();
+            artifact.read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != size || manifest::sha256(&bytes) != sha256 {
+                return Err(anyhow!("Klave API request failed (400): artifact mismatch"));
+            }
+            self.artifacts
+                .borrow_mut()
+                .insert((app.to_string(), sha256.to_string()), bytes);
+            Ok(())
+        }
+
+        fn set_secrets(&self, app: &str, secrets: &BTreeMap<String, Option<String>>) -> Result<()> {
+            let mut all = self.secrets.borrow_mut();
+            let app_secrets = all.entry(app.to_string()).or_default();
+            for (name, value) in secrets {
+                match value {
+                    Some(value) => app_secrets.insert(name.clone(), value.clone()),
+                    None => app_secrets.remove(name),
+                };
+            }
+            Ok(())
+        }
+
+        fn create_deployment(&self, app: &str, request: &DeploymentRequest) -> Result<Deployment> {
+            if !self.has_artifact(app, &request.artifact)? {
+                return Err(anyhow!("Klave API request failed (400): unknown artifact"));
+            }
+            let deployment = Deployment {
+                id: format!("dep-{}", self.deployments.borrow().len() + 1),
+                app: app.to_string(),
+                version: request.version.clone(),
+                status: "running".to_string(),
+                artifact: request.artifact.clone(),
+                created_at: None,
+                url: None,
+            };
+            self.deployments.borrow_mut().push(deployment.clone());
+            self.serving
+                .borrow_mut()
+                .insert(app.to_string(), deployment.id.clone());
+            Ok(deployment)
+        }
+
+        fn app_status(&self, app: &str) -> Result<AppStatus> {
+            let deployment = match self.serving.borrow().get(app) {
+                Some(id) => Some(self.deployment(app, id)?),
+                None => None,
+            };
+            Ok(AppStatus {
+                app: app.to_string(),
+                status: if deployment.is_some() {
+                    "running"
+                } else {
+                    "not deployed"
+                }
+                .to_string(),
+                url: None,
+                deployment,
+            })
+        }
+
+        fn list_deployments(&self, app: &str) -> Result<Vec<Deployment>> {
+            Ok(self
+                .deployments
+                .borrow()
+                .iter()
+                .rev()
+                .filter(|d| d.app == app)
+                .cloned()
+                .collect())
+        }
+
+        /// Pages of one entry, the cursor being the index of the next entry
+        fn logs(&self, _app: &str, cursor: Option<&str>) -> Result<LogPage> {
+            let start: usize = cursor.map_or(Ok(0), str::parse)?;
+            let entries: Vec<LogEntry> = self
+                .logs
+                .borrow()
+                .iter()
+                .skip(start)
+                .take(1)
+                .cloned()
+                .collect();
+            Ok(LogPage {
+                cursor: Some((start + entries.len()).to_string()),
+                entries,
+            })
+        }
+
+        fn rollback(&self, app: &str, deployment: &str) -> Result<Deployment> {
+            let deployment = self.deployment(app, deployment)?;
+            self.serving
+                .borrow_mut()
+                .insert(app.to_string(), deployment.id.clone());
+            Ok(deployment)
+        }
+    }
+}
+
+/// Local HTTP server standing in for the Klave API, for tests
+#[cfg(test)]
+pub mod server {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Answer one request per response on a local port, sending back the
+    /// request line and headers of each request
+    pub fn serve(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    head.push(line.trim_end().to_string());
+                }
+                let length = head
+                    .iter()
+                    .find_map(|h| {
+                        h.to_ascii_lowercase()
+                            .strip_prefix("content-length: ")?
+                            .parse()
+                            .ok()
+                    })
+                    .unwrap_or(0);
+                let mut request_body = vec![0; length];
+                reader.read_exact(&mut request_body).unwrap();
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                sender.send(head).unwrap();
+            }
+        });
+
+        (url, receiver)
+    }
+
+    pub fn header<'a>(head: &'a [String], name: &str) -> Option<&'a str> {
+        head.iter().find_map(|h| {
+            let (key, value) = h.split_once(": ")?;
+            key.eq_ignore_ascii_case(name).then_some(value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::server::{header, serve};
+    use super::*;
+
+    fn request(config: Value) -> DeploymentRequest {
+        DeploymentRequest {
+            version: "0.0.1".to_string(),
+            artifact: "ab".repeat(32),
+            config,
+            provenance: None,
+        }
+    }
+
+    const DEPLOYMENT: &str = r#"{"id":"dep-1","app":"my app","status":"running"}"#;
+
+    #[test]
+    fn segment_encodes_reserved_characters() {
+        assert_eq!(segment("hello_world-1.0~"), "hello_world-1.0~");
+        assert_eq!(segment("my app/../x?y"), "my%20app%2F..%2Fx%3Fy");
+    }
+
+    #[test]
+    fn create_deployment_encodes_the_path_and_sends_an_idempotency_key() {
+        let (url, requests) = serve(vec![
+            (201, DEPLOYMENT),
+            (201, DEPLOYMENT),
+            (201, DEPLOYMENT),
+        ]);
+        let client = HttpClient::new(url, Some("token".to_string()));
+
+        let first = request(serde_json::json!({ "slug": "my app" }));
+        let deployment = client.create_deployment("my app", &first).unwrap();
+        assert_eq!(deployment.id, "dep-1");
+        client.create_deployment("my app", &first).unwrap();
+        client
+            .create_deployment(
+                "my app",
+                &request(serde_json::json!({ "slug": "my app", "branch": "main" })),
+            )
+            .unwrap();
+
+        let heads: Vec<Vec<String>> = requests.iter().take(3).collect();
+        assert_eq!(heads[0][0], "POST /v1/apps/my%20app/deployments HTTP/1.1");
+        assert_eq!(header(&heads[0], "Authorization"), Some("Bearer token"));
+
+        // Retries of the same request share a key, another configuration does not
+        let keys: Vec<&str> = heads
+            .iter()
+            .map(|head| header(head, "Idempotency-Key").unwrap())
+            .collect();
+        assert_eq!(keys[0], keys[1]);
+        assert_ne!(keys[0], keys[2]);
+        assert_ne!(keys[0], first.artifact);
+    }
+
+    #[test]
+    fn has_artifact_maps_not_found_to_false() {
+        let (url, requests) = serve(vec![(200, ""), (404, "")]);
+        let client = HttpClient::new(url, None);
+        let sha256 = "ab".repeat(32);
+
+        assert!(client.has_artifact("hello", &sha256).unwrap());
+        assert!(!client.has_artifact("hello", &sha256).unwrap());
+        assert_eq!(
+            requests.recv().unwrap()[0],
+            format!("HEAD /v1/apps/hello/artifacts/{} HTTP/1.1", sha256)
+        );
+    }
+
+    #[test]
+    fn errors_include_the_server_message() {
+        let (url, _requests) = serve(vec![(409, r#"{"message":"deployment in progress"}"#)]);
+        let client = HttpClient::new(url, None);
+
+        let error = client.rollback("hello", "dep-1").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Klave API request failed (409): deployment in progress"
+        );
+    }
+
+    #[test]
+    fn app_status_reads_the_serving_deployment() {
+        let (url, requests) = serve(vec![(
+            200,
+            r#"{"app":"my app","status":"running","url":"https://my-app.klave.network","deployment":{"id":"dep-1","app":"my app","status":"running"}}"#,
+        )]);
+        let client = HttpClient::new(url, Some("token".to_string()));
+
+        let status = client.app_status("my app").unwrap();
+        assert_eq!(status.status, "running");
+        assert_eq!(status.deployment.unwrap().id, "dep-1");
+
+        let head = requests.recv().unwrap();
+        assert_eq!(head[0], "GET /v1/apps/my%20app HTTP/1.1");
+        assert_eq!(header(&head, "Authorization"), Some("Bearer token"));
+    }
+
+    #[test]
+    fn list_deployments_reads_every_deployment() {
+        let (url, requests) = serve(vec![(
+            200,
+            r#"[{"id":"dep-2","app":"hello","version":"0.0.2","status":"running"},{"id":"dep-1","app":"hello","status":"superseded","createdAt":"2026-01-01T00:00:00Z"}]"#,
+        )]);
+        let client = HttpClient::new(url, None);
+
+        let deployments = client.list_deployments("hello").unwrap();
+        assert_eq!(deployments.len(), 2);
+        assert_eq!(deployments[0].version, "0.0.2");
+        assert_eq!(
+            deployments[1].created_at.as_deref(),
+            Some("2026-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            requests.recv().unwrap()[0],
+            "GET /v1/apps/hello/deployments HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn logs_send_the_cursor() {
+        let (url, requests) = serve(vec![
+            (
+                200,
+                r#"{"entries":[{"timestamp":"2026-01-01T00:00:00Z","level":"info","message":"started"}],"cursor":"c/1"}"#,
+            ),
+            (200, r#"{"entries":[]}"#),
+        ]);
+        let client = HttpClient::new(url, None);
+
+        let page = client.logs("hello", None).unwrap();
+        assert_eq!(page.entries[0].message, "started");
+        assert_eq!(page.cursor.as_deref(), Some("c/1"));
+        let page = client.logs("hello", page.cursor.as_deref()).unwrap();
+        assert!(page.entries.is_empty());
+        assert!(page.cursor.is_none());
+
+        let heads: Vec<Vec<String>> = requests.iter().take(2).collect();
+        assert_eq!(heads[0][0], "GET /v1/apps/hello/logs HTTP/1.1");
+        assert_eq!(heads[1][0], "GET /v1/apps/hello/logs?cursor=c%2F1 HTTP/1.1");
+    }
+}
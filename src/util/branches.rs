@@ -0,0 +1,247 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use glob::Pattern;
+use serde_json::Value;
+use std::env;
+use std::path::Path;
+
+use crate::util::{git, project};
+
+/// Branch the project is built from
+///
+/// `KLAVE_BRANCH` takes precedence over git, for CI checkouts on a detached
+/// HEAD.
+pub fn current_branch(cwd: &Path) -> Option<String> {
+    env::var("KLAVE_BRANCH")
+        .ok()
+        .filter(|branch| !branch.is_empty())
+        .or_else(|| git::current_branch(cwd))
+}
+
+/// Whether a branch matches a glob pattern, where `*` also matches `/`
+fn matches(pattern: &str, branch: &str) -> bool {
+    Pattern::new(pattern)
+        .map(|pattern| pattern.matches(branch))
+        .unwrap_or(pattern == branch)
+}
+
+/// Read a `branches` list, None when absent
+fn patterns(value: &Value) -> Result<Option<Vec<&str>>> {
+    match value.get("branches") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .ok_or_else(|| anyhow!("Invalid branch pattern {} in klave.json", item))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some),
+        Some(other) => Err(anyhow!(
+            "Invalid branches {} in klave.json, expected a list of glob patterns",
+            other
+        )),
+    }
+}
+
+/// Whether a pattern matches every branch
+fn is_catch_all(pattern: &str) -> bool {
+    !pattern.is_empty() && pattern.chars().all(|c| c == '*')
+}
+
+/// `branches` of an application, or of the project when it has none
+fn app_patterns<'a>(config: &'a Value, application: &'a Value) -> Result<Option<Vec<&'a str>>> {
+    match patterns(application)? {
+        Some(patterns) => Ok(Some(patterns)),
+        None => patterns(config),
+    }
+}
+
+/// Whether an application may be deployed from a branch
+///
+/// The application's own `branches` take precedence over the project's. A
+/// missing list allows every branch.
+pub fn is_deployable(config: &Value, application: &Value, branch: &str) -> Result<bool> {
+    Ok(app_patterns(config, application)?
+        .is_none_or(|patterns| patterns.iter().any(|p| matches(p, branch))))
+}
+
+/// Apply the `branchOverrides` of an application for a branch
+///
+/// Overrides are keyed by branch pattern and merged over the application's
+/// settings. Patterns with wildcards apply first so that an exact branch name
+/// has the last word.
+pub fn apply_overrides(application: &Value, branch: &str) -> Result<Value> {
+    let mut resolved = application.clone();
+    let Some(overrides) = resolved
+        .as_object_mut()
+        .and_then(|app| app.remove("branchOverrides"))
+    else {
+        return Ok(resolved);
+    };
+
+    let overrides = overrides.as_object().ok_or_else(|| {
+        anyhow!(
+            "Invalid branchOverrides for \"{}\", expected an object keyed by branch pattern",
+            project::app_slug(application)
+        )
+    })?;
+
+    let mut matching: Vec<(&String, &Value)> = overrides
+        .iter()
+        .filter(|(pattern, _)| matches(pattern, branch))
+        .collect();
+    matching.sort_by_key(|(pattern, _)| !pattern.contains(['*', '?', '[']));
+
+    for (_, settings) in matching {
+        project::merge(&mut resolved, settings);
+    }
+
+    Ok(resolved)
+}
+
+/// Whether the branch matters for these applications: some have
+/// `branchOverrides` or `branches` without a catch-all pattern such as `*`
+fn has_branch_rules(config: &Value, applications: &[&Value]) -> Result<bool> {
+    for application in applications {
+        if application.get("branchOverrides").is_some() {
+            return Ok(true);
+        }
+        if app_patterns(config, application)?
+            .is_some_and(|patterns| !patterns.iter().any(|p| is_catch_all(p)))
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Resolve applications for the current branch
+///
+/// Applies per-branch overrides and checks each application may be deployed
+/// from the branch. When `strict`, applications that may not are an error,
+/// otherwise a warning. So is an unknown branch when klave.json has branch
+/// rules, since they cannot be applied; lists that allow every branch are
+/// not rules.
+pub fn resolve_apps(
+    cwd: &Path,
+    config: &Value,
+    applications: &[&Value],
+    strict: bool,
+) -> Result<Vec<Value>> {
+    let Some(branch) = current_branch(cwd) else {
+        if has_branch_rules(config, applications)? {
+            if strict {
+                return Err(anyhow!(
+                    "Could not determine the git branch to apply the branch rules of klave.json, set KLAVE_BRANCH"
+                ));
+            }
+            eprintln!(
+                "{}",
+                "Warning: Could not determine the git branch, set KLAVE_BRANCH to check klave.json branches"
+                    .yellow()
+            );
+        }
+        return Ok(applications.iter().map(|app| (*app).clone()).collect());
+    };
+
+    let mut refused = Vec::new();
+    let mut resolved = Vec::new();
+    for application in applications {
+        if !is_deployable(config, application, &branch)? {
+            refused.push(project::app_slug(application));
+        }
+        resolved.push(apply_overrides(application, &branch)?);
+    }
+
+    if !refused.is_empty() {
+        let message = format!(
+            "Branch \"{}\" is not deployable for: {} (see \"branches\" in klave.json)",
+            branch,
+            refused.join(", ")
+        );
+        if strict {
+            return Err(anyhow!(message));
+        }
+        eprintln!("{}", format!("Warning: {}", message).yellow());
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_globs_across_slashes() {
+        assert!(matches("main", "main"));
+        assert!(!matches("main", "main-2"));
+        assert!(matches("*", "feature/login/form"));
+        assert!(matches("feature/*", "feature/login/form"));
+        assert!(!matches("feature/*", "fix/login"));
+        assert!(matches("release-?.?", "release-1.2"));
+        assert!(matches("v[0-9]*", "v10"));
+        // An invalid glob only matches itself
+        assert!(matches("[main", "[main"));
+        assert!(!matches("[main", "main"));
+    }
+
+    #[test]
+    fn app_branches_take_precedence() {
+        let config = json!({ "branches": ["main"] });
+
+        assert!(is_deployable(&config, &json!({}), "main").unwrap());
+        assert!(!is_deployable(&config, &json!({}), "dev").unwrap());
+        let app = json!({ "branches": ["dev", "feature/*"] });
+        assert!(is_deployable(&config, &app, "feature/x").unwrap());
+        assert!(!is_deployable(&config, &app, "main").unwrap());
+
+        assert!(is_deployable(&json!({}), &json!({}), "anything").unwrap());
+        assert!(!is_deployable(&json!({}), &json!({ "branches": [] }), "main").unwrap());
+        assert!(is_deployable(&json!({}), &json!({ "branches": ["main", 1] }), "main").is_err());
+    }
+
+    #[test]
+    fn exact_overrides_apply_after_wildcards() {
+        // "release" sorts before "release*" in the object
+        let app = json!({
+            "slug": "hello",
+            "maxSize": 1,
+            "branchOverrides": {
+                "release": { "maxSize": 3 },
+                "release*": { "maxSize": 2, "name": "Release" },
+                "main": { "maxSize": 4 }
+            }
+        });
+
+        assert_eq!(
+            apply_overrides(&app, "release").unwrap(),
+            json!({ "slug": "hello", "maxSize": 3, "name": "Release" })
+        );
+        assert_eq!(
+            apply_overrides(&app, "release-2").unwrap(),
+            json!({ "slug": "hello", "maxSize": 2, "name": "Release" })
+        );
+        assert_eq!(
+            apply_overrides(&app, "dev").unwrap(),
+            json!({ "slug": "hello", "maxSize": 1 })
+        );
+    }
+
+    #[test]
+    fn catch_all_branches_are_not_rules() {
+        let any = json!({ "branches": ["*"] });
+        let main = json!({ "branches": ["main"] });
+        let none = json!({});
+
+        assert!(!has_branch_rules(&any, &[&none]).unwrap());
+        assert!(!has_branch_rules(&none, &[&json!({ "branches": ["**", "main"] })]).unwrap());
+        assert!(!has_branch_rules(&main, &[&any]).unwrap());
+        assert!(has_branch_rules(&any, &[&none, &main]).unwrap());
+        assert!(has_branch_rules(&none, &[&json!({ "branches": [] })]).unwrap());
+        assert!(has_branch_rules(&any, &[&json!({ "branchOverrides": { "*": {} } })]).unwrap());
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result, anyhow};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder, HeaderMode};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::util::git;
+use crate::util::manifest::{self, Manifest};
+
+/// Name of the resolved configuration inside a bundle
+pub const CONFIG_FILE: &str = "klave.json";
+
+/// Name of the manifest inside a bundle
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Directory of the source tarballs inside a bundle
+pub const SOURCES_DIR: &str = "sources";
+
+/// Directories left out of source tarballs
+const IGNORED_SOURCE_DIRS: &[&str] = &[".git", ".klave", "node_modules", "target"];
+
+/// Whether a file is left out of source tarballs
+///
+/// Besides build outputs and dependencies, `.env` files are left out as they
+/// usually hold credentials.
+fn is_ignored_source(file: &Path) -> bool {
+    file.components()
+        .any(|c| IGNORED_SOURCE_DIRS.contains(&c.as_os_str().to_string_lossy().as_ref()))
+        || file
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .is_some_and(|name| name == ".env" || name.starts_with(".env."))
+}
+
+/// Files of a directory, relative to it, without descending into `skipped_dirs`
+fn all_files(dir: &Path, skipped_dirs: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            !(entry.file_type().is_dir()
+                && skipped_dirs.contains(&entry.file_name().to_string_lossy().as_ref()))
+        });
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            files.push(entry.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+/// Write files of `dir` as a gzipped tarball
+///
+/// Entries are sorted and their metadata normalised so packing the same
+/// files twice gives the same archive.
+fn write_tarball(dir: &Path, mut files: Vec<PathBuf>, output: &Path) -> Result<()> {
+    files.sort();
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::create(output).context(format!("Failed to create {:?}", output))?;
+    let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.mode(HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+
+    for name in files {
+        builder.append_path_with_name(dir.join(&name), &name)?;
+    }
+
+    builder
+        .into_inner()?
+        .finish()
+        .context(format!("Failed to write {:?}", output))?;
+    Ok(())
+}
+
+/// Write a bundle from its staging directory
+pub fn write_bundle(dir: &Path, output: &Path) -> Result<()> {
+    write_tarball(dir, all_files(dir, &[])?, output)
+}
+
+/// Write the sources of an application as a gzipped tarball
+///
+/// In a git repository only the files git does not ignore are included.
+/// Build outputs, dependencies and `.env` files are always left out.
+pub fn write_sources(app_dir: &Path, output: &Path) -> Result<()> {
+    let files = match git::list_files(app_dir) {
+        Some(files) => files
+            .into_iter()
+            .filter(|file| app_dir.join(file).symlink_metadata().is_ok())
+            .collect(),
+        None => all_files(app_dir, IGNORED_SOURCE_DIRS)?,
+    };
+    let files = files
+        .into_iter()
+        .filter(|file| !is_ignored_source(file))
+        .collect();
+    write_tarball(app_dir, files, output)
+}
+
+/// Extract a gzipped tarball into `dir`
+///
+/// Entries escaping `dir` are refused by the tar reader.
+pub fn extract(bundle: &Path, dir: &Path) -> Result<()> {
+    let file = File::open(bundle).context(format!("Failed to open {:?}", bundle))?;
+    fs::create_dir_all(dir)?;
+    Archive::new(GzDecoder::new(file))
+        .unpack(dir)
+        .context(format!(
+            "Failed to extract {:?}, is it a bundle from 'klave pack'?",
+            bundle
+        ))
+}
+
+/// An extracted bundle whose files were checked against its manifest
+pub struct Bundle {
+    pub dir: PathBuf,
+    pub manifest: Manifest,
+    pub config: Value,
+    /// Keeps a temporary extraction directory alive
+    _temp: Option<TempDir>,
+}
+
+impl Bundle {
+    /// Extract a bundle into a temporary directory and check it
+    pub fn open(bundle: &Path) -> Result<Self> {
+        let temp = tempfile::tempdir()?;
+        extract(bundle, temp.path())?;
+        let mut bundle = Self::check(temp.path())?;
+        bundle._temp = Some(temp);
+        Ok(bundle)
+    }
+
+    /// Check an extracted bundle: every file must match the manifest, and
+    /// the manifest signature must be valid when there is one
+    pub fn check(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Err(anyhow!("Not a Klave bundle: no {} found", MANIFEST_FILE));
+        }
+        let manifest = manifest::read(&manifest_path)?;
+
+        if manifest.signature.is_some() {
+            manifest::verify_signature(&manifest)?;
+        }
+
+        let files = manifest
+            .artifacts
+            .iter()
+            .map(|a| (&a.file, &a.sha256))
+            .chain(manifest.files.iter().map(|f| (&f.file, &f.sha256)));
+        for (file, sha256) in files {
+            manifest::check_file(dir, file, sha256)
+                .map_err(|error| anyhow!("Bundle file {} is corrupted: {}", file, error))?;
+        }
+
+        if !manifest.files.iter().any(|f| f.file == CONFIG_FILE) {
+            return Err(anyhow!(
+                "The bundle manifest does not cover {}",
+                CONFIG_FILE
+            ));
+        }
+        let config = serde_json::from_str(&fs::read_to_string(dir.join(CONFIG_FILE))?)
+            .context(format!("Invalid JSON in the bundled {}", CONFIG_FILE))?;
+
+        Ok(Bundle {
+            dir: dir.to_path_buf(),
+            manifest,
+            config,
+            _temp: None,
+        })
+    }
+
+    /// Path of the artifact of an application
+    pub fn artifact_path(&self, slug: &str) -> Result<PathBuf> {
+        self.manifest
+            .artifacts
+            .iter()
+            .find(|a| a.slug == slug)
+            .map(|a| self.dir.join(&a.file))
+            .ok_or_else(|| anyhow!("The bundle has no artifact for \"{}\"", slug))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(tarball: &Path) -> Vec<String> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(tarball).unwrap()));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sources_leave_out_env_files_and_build_outputs() {
+        let app = tempfile::tempdir().unwrap();
+        for file in [
+            "src/index.ts",
+            "package.json",
+            ".env",
+            ".env.production",
+            "node_modules/dep/index.js",
+            "target/release/app.wasm",
+        ] {
+            let path = app.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+
+        let out = tempfile::tempdir().unwrap();
+        let first = out.path().join("first.tar.gz");
+        let second = out.path().join("second.tar.gz");
+        write_sources(app.path(), &first).unwrap();
+        write_sources(app.path(), &second).unwrap();
+
+        assert_eq!(entries(&first), ["package.json", "src/index.ts"]);
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+    }
+}
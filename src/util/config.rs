@@ -0,0 +1,123 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory holding the per-user CLI configuration
+///
+/// Defaults to the platform config directory and can be overridden with
+/// `KLAVE_CONFIG_DIR`. It holds keys and credentials, so when the CLI creates
+/// it it is restricted to the current user. An existing directory is left
+/// as is, with a warning if other users can access it.
+pub fn config_dir() -> Result<PathBuf> {
+    let dir = match env::var("KLAVE_CONFIG_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => dirs::config_dir()
+            .map(|dir| dir.join("klave"))
+            .ok_or_else(|| anyhow!("Could not determine the user configuration directory"))?,
+    };
+
+    if dir.exists() {
+        warn_if_shared(&dir);
+        return Ok(dir);
+    }
+
+    fs::create_dir_all(&dir).context(format!("Failed to create {:?}", dir))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(dir)
+}
+
+/// Warn, once per run, about a configuration directory other users can access
+fn warn_if_shared(dir: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Once;
+
+        static WARNED: Once = Once::new();
+        let Ok(metadata) = fs::metadata(dir) else {
+            return;
+        };
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            WARNED.call_once(|| {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Warning: {:?} is accessible by other users (mode {:o}), restrict it with: chmod 700 {}",
+                        dir,
+                        mode & 0o777,
+                        dir.display()
+                    )
+                    .yellow()
+                )
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+/// Write a file readable by the current user only
+///
+/// The file is created with mode 0600 and an existing file is restricted
+/// before it is written. Missing parent directories are created, but the
+/// permissions of the parent are left to the caller.
+pub fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .context(format!("Failed to write {:?}", path))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents)
+        .context(format!("Failed to write {:?}", path))
+}
+
+/// Refuse to use a private file other users can access
+pub fn check_private_file(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)
+            .context(format!("Failed to read {:?}", path))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            return Err(anyhow!(
+                "{:?} is accessible by other users (mode {:o}), restrict it with: chmod 600 {}",
+                path,
+                mode & 0o777,
+                path.display()
+            ));
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
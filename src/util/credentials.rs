@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::util::config;
+
+/// Account a token belongs to
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub id: String,
+    pub email: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Token stored by `klave login`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credentials {
+    /// API the token was issued by
+    pub api_url: String,
+    pub token: String,
+    pub account: Account,
+}
+
+/// Where the token in use comes from
+pub enum TokenSource {
+    Environment,
+    File(PathBuf),
+}
+
+fn credentials_path() -> Result<PathBuf> {
+    Ok(config::config_dir()?.join("credentials.json"))
+}
+
+/// Read the stored credentials, if logged in
+pub fn load() -> Result<Option<Credentials>> {
+    let path = credentials_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    config::check_private_file(&path)?;
+    let credentials = serde_json::from_str(
+        &fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?,
+    )
+    .context(format!("Invalid credentials file {:?}", path))?;
+
+    Ok(Some(credentials))
+}
+
+pub fn save(credentials: &Credentials) -> Result<PathBuf> {
+    let path = credentials_path()?;
+    config::write_private_file(&path, serde_json::to_string_pretty(credentials)?.as_bytes())?;
+    Ok(path)
+}
+
+/// Remove the stored credentials, returning whether there were any
+pub fn clear() -> Result<bool> {
+    let path = credentials_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    fs::remove_file(&path).context(format!("Failed to delete {:?}", path))?;
+    Ok(true)
+}
+
+/// Token to authenticate with an API
+///
+/// `KLAVE_TOKEN` takes precedence. A stored token is only sent to the API
+/// that issued it.
+pub fn token(api_url: &str) -> Result<Option<(String, TokenSource)>> {
+    select_token(env::var("KLAVE_TOKEN").ok(), api_url, || {
+        let path = credentials_path()?;
+        Ok(load()?.map(|credentials| (credentials, path)))
+    })
+}
+
+/// Pick between a `KLAVE_TOKEN` value and the stored credentials, reading
+/// the stored credentials only when the variable is unset or blank
+pub fn select_token(
+    env_token: Option<String>,
+    api_url: &str,
+    stored: impl FnOnce() -> Result<Option<(Credentials, PathBuf)>>,
+) -> Result<Option<(String, TokenSource)>> {
+    if let Some(token) = env_token.filter(|token| !token.trim().is_empty()) {
+        return Ok(Some((token.trim().to_string(), TokenSource::Environment)));
+    }
+
+    Ok(stored()?
+        .filter(|(credentials, _)| credentials.api_url == api_url)
+        .map(|(credentials, path)| (credentials.token, TokenSource::File(path))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(api_url: &str, token: &str) -> Result<Option<(Credentials, PathBuf)>> {
+        Ok(Some((
+            Credentials {
+                api_url: api_url.to_string(),
+                token: token.to_string(),
+                account: Account {
+                    id: "acc-1".to_string(),
+                    email: "dev@example.com".to_string(),
+                    name: None,
+                },
+            },
+            PathBuf::from("credentials.json"),
+        )))
+    }
+
+    #[test]
+    fn klave_token_takes_precedence_without_reading_the_file() {
+        let (token, source) = select_token(Some(" from-env\n".to_string()), "https://a", || {
+            panic!("read the stored credentials")
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(token, "from-env");
+        assert!(matches!(source, TokenSource::Environment));
+    }
+
+    #[test]
+    fn blank_klave_token_falls_back_to_the_stored_token() {
+        let (token, source) = select_token(Some("  ".to_string()), "https://a", || {
+            stored("https://a", "stored")
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(token, "stored");
+        assert!(matches!(source, TokenSource::File(path) if path.ends_with("credentials.json")));
+    }
+
+    #[test]
+    fn stored_token_is_only_used_for_its_api() {
+        let token = select_token(None, "https://b", || stored("https://a", "stored")).unwrap();
+        assert!(token.is_none());
+    }
+}
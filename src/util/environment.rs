@@ -0,0 +1,373 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::util::interpolate::{self, Mode};
+use crate::util::project;
+
+/// A source of configuration merged into the resolved klave.json
+pub struct Layer {
+    /// Where the layer comes from, e.g. `klave.prod.json`
+    pub source: String,
+    pub value: Value,
+}
+
+/// Environment to apply: the one given on the command line, or `KLAVE_ENV`
+///
+/// The variable lets commands without an `--env` flag, such as `status` or
+/// `secrets`, work on the same environment as `build` and `deploy`.
+pub fn selected(env: Option<&str>) -> Option<String> {
+    env.map(str::to_string)
+        .or_else(|| env::var("KLAVE_ENV").ok().filter(|env| !env.is_empty()))
+}
+
+/// Names of the environments a project defines, in klave.json or as overlay files
+pub fn available(cwd: &Path, base: &Value) -> Vec<String> {
+    let mut names: Vec<String> = base
+        .get("environments")
+        .and_then(|e| e.as_object())
+        .map(|e| e.keys().cloned().collect())
+        .unwrap_or_default();
+
+    if let Ok(entries) = fs::read_dir(cwd) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name
+                .strip_prefix("klave.")
+                .and_then(|rest| rest.strip_suffix(".json"))
+            {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Load the layers making up the configuration of an environment
+///
+/// The base klave.json comes first, then its `environments.<env>` section,
+/// then `klave.<env>.json`. Without `env`, `KLAVE_ENV` is used if set.
+pub fn layers(cwd: &Path, env: Option<&str>) -> Result<Vec<Layer>> {
+    let env = selected(env);
+    let mut base = project::read_config(cwd)?;
+    let sections = base
+        .as_object_mut()
+        .and_then(|config| config.remove("environments"));
+
+    let mut layers = vec![Layer {
+        source: "klave.json".to_string(),
+        value: base,
+    }];
+
+    let Some(env) = env.as_deref() else {
+        return Ok(layers);
+    };
+
+    if let Some(section) = sections.as_ref().and_then(|s| s.get(env)) {
+        layers.push(Layer {
+            source: format!("klave.json (environments.{})", env),
+            value: section.clone(),
+        });
+    }
+
+    let overlay_name = format!("klave.{}.json", env);
+    let overlay_path = cwd.join(&overlay_name);
+    if overlay_path.exists() {
+        let content = fs::read_to_string(&overlay_path)
+            .context(format!("Failed to read {}", overlay_name))?;
+        layers.push(Layer {
+            value: serde_json::from_str(&content)
+                .context(format!("Invalid JSON in {}", overlay_name))?,
+            source: overlay_name,
+        });
+    }
+
+    if layers.len() == 1 {
+        let mut base = layers.remove(0).value;
+        if let (Some(config), Some(sections)) = (base.as_object_mut(), sections) {
+            config.insert("environments".to_string(), sections);
+        }
+        let names = available(cwd, &base);
+        return Err(anyhow!(
+            "Unknown environment \"{}\": no klave.{}.json or environments.{} in klave.json. Available environments: {}",
+            env,
+            env,
+            env,
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        ));
+    }
+
+    Ok(layers)
+}
+
+/// A resolved value: its path, the value, and the layer it comes from
+pub type SourcedValue = (String, Value, String);
+
+/// Merge a layer into the configuration
+///
+/// Applications are matched by slug, so a layer only needs to list the
+/// settings it changes. Applications unknown to the base are added.
+fn merge_layer(config: &mut Value, layer: &Value) {
+    let Some(layer) = layer.as_object() else {
+        return;
+    };
+
+    for (key, value) in layer {
+        if key == "applications" {
+            if let (Some(Value::Array(apps)), Value::Array(overlays)) =
+                (config.get_mut("applications"), value)
+            {
+                for overlay in overlays {
+                    let slug = project::app_slug(overlay);
+                    match apps.iter_mut().find(|app| project::app_slug(app) == slug) {
+                        Some(app) => project::merge(app, overlay),
+                        None => apps.push(overlay.clone()),
+                    }
+                }
+                continue;
+            }
+        }
+
+        match config.as_object_mut() {
+            Some(config) => project::merge(config.entry(key.clone()).or_insert(Value::Null), value),
+            None => *config = Value::Object(layer.clone()),
+        }
+    }
+}
+
+/// Collect the paths of the leaf values of a configuration
+///
+/// Applications are addressed by slug, e.g. `applications[hello].version`.
+fn leaves(value: &Value, path: String, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                if path == "applications" {
+                    if let Value::Array(apps) = value {
+                        for app in apps {
+                            leaves(
+                                app,
+                                format!("applications[{}]", project::app_slug(app)),
+                                out,
+                            );
+                        }
+                        continue;
+                    }
+                }
+                leaves(value, path, out);
+            }
+        }
+        _ => out.push((path, value.clone())),
+    }
+}
+
+/// Resolve an environment and record which layer each value comes from
+///
+/// With `Mode::Mask`, variable references are checked but left unexpanded so
+/// values from the environment or `.env` are not displayed.
+pub fn resolve_with_sources(
+    cwd: &Path,
+    env: Option<&str>,
+    mode: Mode,
+) -> Result<(Value, Vec<SourcedValue>)> {
+    let layers = layers(cwd, env)?;
+
+    let mut sources: BTreeMap<String, String> = BTreeMap::new();
+    let mut config = Value::Null;
+    for layer in &layers {
+        let mut paths = Vec::new();
+        leaves(&layer.value, String::new(), &mut paths);
+        for (path, _) in paths {
+            // The slug only identifies which application a layer amends
+            if path.ends_with("].slug") && sources.contains_key(&path) {
+                continue;
+            }
+            sources.insert(path, layer.source.clone());
+        }
+
+        if config.is_null() {
+            config = layer.value.clone();
+        } else {
+            merge_layer(&mut config, &layer.value);
+        }
+    }
+
+    interpolate::resolve(cwd, &mut config, mode)?;
+
+    let mut resolved = Vec::new();
+    leaves(&config, String::new(), &mut resolved);
+    let resolved = resolved
+        .into_iter()
+        .map(|(path, value)| {
+            let source = sources.get(&path).cloned().unwrap_or_default();
+            (path, value, source)
+        })
+        .collect();
+
+    Ok((config, resolved))
+}
+
+/// Read klave.json with the overlays of an environment applied and variables expanded
+pub fn resolve(cwd: &Path, env: Option<&str>) -> Result<Value> {
+    Ok(resolve_with_sources(cwd, env, Mode::Expand)?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn project(files: &[(&str, Value)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, value) in files {
+            fs::write(dir.path().join(name), value.to_string()).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn merge_layer_matches_applications_by_slug() {
+        let mut config = json!({
+            "version": "1",
+            "branches": ["main"],
+            "applications": [
+                { "slug": "hello", "version": "0.0.1", "rootDir": "/apps/hello" },
+                { "slug": "world", "version": "0.0.1" }
+            ]
+        });
+        merge_layer(
+            &mut config,
+            &json!({
+                "branches": ["release/*"],
+                "applications": [
+                    { "slug": "world", "version": "0.0.2" },
+                    { "slug": "extra", "version": "0.1.0" }
+                ]
+            }),
+        );
+
+        assert_eq!(
+            config,
+            json!({
+                "version": "1",
+                "branches": ["release/*"],
+                "applications": [
+                    { "slug": "hello", "version": "0.0.1", "rootDir": "/apps/hello" },
+                    { "slug": "world", "version": "0.0.2" },
+                    { "slug": "extra", "version": "0.1.0" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn overlay_file_applies_after_the_environments_section() {
+        let dir = project(&[
+            (
+                "klave.json",
+                json!({
+                    "version": "1",
+                    "applications": [{ "slug": "hello", "version": "0.0.1", "maxSize": 100 }],
+                    "environments": {
+                        "prod": { "applications": [{ "slug": "hello", "version": "0.0.2", "maxSize": 200 }] }
+                    }
+                }),
+            ),
+            (
+                "klave.prod.json",
+                json!({ "applications": [{ "slug": "hello", "version": "0.0.3" }] }),
+            ),
+        ]);
+
+        let config = resolve(dir.path(), Some("prod")).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "version": "1",
+                "applications": [{ "slug": "hello", "version": "0.0.3", "maxSize": 200 }]
+            })
+        );
+        assert_eq!(
+            available(dir.path(), &project::read_config(dir.path()).unwrap()),
+            ["prod"]
+        );
+
+        let error = resolve(dir.path(), Some("staging")).err().unwrap();
+        assert!(error.to_string().ends_with("Available environments: prod"));
+    }
+
+    #[test]
+    fn leaves_address_applications_by_slug() {
+        let mut out = Vec::new();
+        leaves(
+            &json!({
+                "version": "1",
+                "environments": {},
+                "applications": [{ "slug": "hello", "config": { "retries": 3 } }]
+            }),
+            String::new(),
+            &mut out,
+        );
+
+        assert_eq!(
+            out,
+            [
+                ("applications[hello].config.retries".to_string(), json!(3)),
+                ("applications[hello].slug".to_string(), json!("hello")),
+                ("environments".to_string(), json!({})),
+                ("version".to_string(), json!("1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn sources_name_the_last_layer_setting_each_value() {
+        let dir = project(&[
+            (
+                "klave.json",
+                json!({
+                    "version": "1",
+                    "applications": [{ "slug": "hello", "version": "0.0.1", "maxSize": 100 }],
+                    "environments": {
+                        "prod": { "applications": [{ "slug": "hello", "maxSize": 200 }] }
+                    }
+                }),
+            ),
+            (
+                "klave.prod.json",
+                json!({ "applications": [{ "slug": "hello", "version": "0.0.3" }] }),
+            ),
+        ]);
+
+        let (_, sourced) = resolve_with_sources(dir.path(), Some("prod"), Mode::Mask).unwrap();
+        let source = |path: &str| {
+            sourced
+                .iter()
+                .find(|(p, _, _)| p == path)
+                .map(|(_, _, source)| source.as_str())
+                .unwrap()
+        };
+        assert_eq!(source("version"), "klave.json");
+        assert_eq!(source("applications[hello].slug"), "klave.json");
+        assert_eq!(
+            source("applications[hello].maxSize"),
+            "klave.json (environments.prod)"
+        );
+        assert_eq!(source("applications[hello].version"), "klave.prod.json");
+    }
+}
@@ -1,5 +1,5 @@
 use std::process::Command;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::env;
 use ureq;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -7,7 +7,7 @@ use indicatif::{ProgressBar, ProgressStyle};
 /// Finds user's name by reading it from the git config.
 pub fn find_my_name() -> String {
     match Command::new("git")
-        .args(&["config", "--get", "user.name"])
+        .args(["config", "--get", "user.name"])
         .output()
     {
         Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
@@ -18,7 +18,7 @@ pub fn find_my_name() -> String {
 /// Finds user's email by reading it from the git config.
 pub fn find_github_email() -> String {
     match Command::new("git")
-        .args(&["config", "--get", "user.email"])
+        .args(["config", "--get", "user.email"])
         .output()
     {
         Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
@@ -91,7 +91,7 @@ pub async fn create_git_repo_async(target_dir: &str) -> Result<(), Box<dyn std::
     pb.set_message("Creating an empty Git repository");
     
     let output = Command::new("git")
-        .args(&["init"])
+        .args(["init"])
         .current_dir(target_dir)
         .output()?;
     
@@ -102,4 +102,97 @@ pub async fn create_git_repo_async(target_dir: &str) -> Result<(), Box<dyn std::
     
     pb.finish_with_message("Created an empty Git repository");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Get the commit currently checked out in a repository.
+pub fn current_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Check whether a repository has uncommitted changes.
+///
+/// The `.klave` directory of `dir` is left out: it holds what `klave build`
+/// itself writes, which would otherwise mark every build as dirty.
+pub fn is_dirty(dir: &Path) -> Option<bool> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain", "--", ":/", ":(exclude).klave"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(!output.stdout.is_empty())
+}
+
+/// List the files of a directory that git does not ignore, relative to it.
+///
+/// Tracked and untracked files are included, None outside a repository.
+pub fn list_files(dir: &Path) -> Option<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|file| !file.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+/// Get the commit time of HEAD as a unix timestamp.
+pub fn commit_timestamp(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["log", "-1", "--format=%ct"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Get the branch currently checked out, None on a detached HEAD.
+pub fn current_branch(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (branch != "HEAD").then_some(branch)
+}
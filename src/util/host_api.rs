@@ -0,0 +1,390 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::util::project;
+use crate::util::wasm::{self, WasmKind};
+
+/// Description of the host functions provided by the Klave platform
+static HOST_API: &str = include_str!("../../host-api/klave.json");
+
+#[derive(Deserialize)]
+struct HostApi {
+    sdks: HashMap<String, Sdk>,
+    forbidden: Vec<Forbidden>,
+}
+
+#[derive(Deserialize)]
+struct Sdk {
+    package: String,
+    versions: Vec<SdkVersion>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SdkVersion {
+    range: String,
+    /// Host functions by core module name (`env`) or by component interface
+    imports: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Forbidden {
+    pattern: String,
+    reason: String,
+    #[serde(default)]
+    likely_sources: Vec<String>,
+}
+
+/// Match a name against a pattern where `*` matches any sequence of characters
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| matches(rest, &name[i..]))
+        }
+    }
+}
+
+/// Name of a component interface without its version, e.g. `wasi:io/streams`
+/// for `wasi:io/streams@0.2.0`
+fn interface_name(name: &str) -> &str {
+    name.split_once('@').map_or(name, |(name, _)| name)
+}
+
+/// Whether a function imported from a core module or interface is provided by the host
+fn is_host_function(host: &BTreeMap<String, Vec<String>>, module: &str, name: &str) -> bool {
+    host.get(interface_name(module))
+        .is_some_and(|functions| functions.iter().any(|pattern| matches(pattern, name)))
+}
+
+/// Find the installed SDK version of an application, if it can be determined
+fn sdk_version(cwd: &Path, app_dir: &Path, app_type: &str, package: &str) -> Option<String> {
+    match app_type {
+        "rust" => {
+            let lock = cargo_lock(cwd, app_dir)?;
+            lock.get("package")?
+                .as_array()?
+                .iter()
+                .find(|p| p.get("name").and_then(|n| n.as_str()) == Some(package))?
+                .get("version")?
+                .as_str()
+                .map(|v| v.to_string())
+        }
+        "assemblyscript" => {
+            let manifest =
+                fs::read_to_string(cwd.join("node_modules").join(package).join("package.json"))
+                    .ok()?;
+            serde_json::from_str::<Value>(&manifest)
+                .ok()?
+                .get("version")?
+                .as_str()
+                .map(|v| v.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn cargo_lock(cwd: &Path, app_dir: &Path) -> Option<toml::Value> {
+    let workspace_dir = project::cargo_workspace_dir(cwd, app_dir);
+    fs::read_to_string(workspace_dir.join("Cargo.lock"))
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Find how a Rust application depends on a crate, e.g. `my_app -> rand -> getrandom`
+fn cargo_dependency_path(lock: &toml::Value, root: &str, target: &str) -> Option<String> {
+    let packages = lock.get("package")?.as_array()?;
+    let dependencies: HashMap<&str, Vec<&str>> = packages
+        .iter()
+        .filter_map(|p| {
+            let name = p.get("name")?.as_str()?;
+            let deps = p
+                .get("dependencies")
+                .and_then(|d| d.as_array())
+                .map(|d| {
+                    d.iter()
+                        .filter_map(|d| d.as_str()?.split(' ').next())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some((name, deps))
+        })
+        .collect();
+
+    // Breadth-first search so the shortest path is reported
+    let mut parents: HashMap<&str, &str> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+
+    while let Some(name) = queue.pop_front() {
+        if name == target {
+            let mut path = vec![name];
+            let mut current = name;
+            while let Some(parent) = parents.get(current) {
+                path.push(parent);
+                current = parent;
+            }
+            path.reverse();
+            return Some(path.join(" -> "));
+        }
+
+        for dep in dependencies.get(name).into_iter().flatten() {
+            if visited.insert(dep) {
+                parents.insert(dep, name);
+                queue.push_back(dep);
+            }
+        }
+    }
+
+    None
+}
+
+/// Describe which dependencies of an application likely pulled in an import
+fn likely_sources(
+    cwd: &Path,
+    app_dir: &Path,
+    app_type: &str,
+    candidates: &[String],
+) -> Vec<String> {
+    match app_type {
+        "rust" => {
+            let (Some(lock), Ok(root)) = (
+                cargo_lock(cwd, app_dir),
+                project::cargo_package_name(app_dir),
+            ) else {
+                return Vec::new();
+            };
+            candidates
+                .iter()
+                .filter_map(|c| cargo_dependency_path(&lock, &root, c))
+                .collect()
+        }
+        "assemblyscript" => candidates
+            .iter()
+            .filter(|c| cwd.join("node_modules").join(c).exists())
+            .map(|c| format!("{} (node_modules)", c))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Check the imports of an application's artifact against the Klave host API
+///
+/// Imports matching a forbidden pattern fail the check with an error listing
+/// each offending import and the dependencies that likely pulled it in.
+/// Imports that are neither allowed nor forbidden are returned as warnings.
+/// A component imports whole interfaces, so the functions its core modules
+/// use from the host interfaces are checked as well.
+pub fn check_artifact(cwd: &Path, application: &Value, artifact: &Path) -> Result<Vec<String>> {
+    let host_api: HostApi =
+        serde_json::from_str(HOST_API).context("Invalid bundled host API description")?;
+
+    let app_dir = project::app_dir(cwd, application);
+    let app_type = project::app_type(&app_dir);
+    let sdk = host_api
+        .sdks
+        .get(app_type)
+        .ok_or_else(|| anyhow!("No host API description for {} apps", app_type))?;
+
+    // Pick the description matching the installed SDK, or the latest one
+    let version = sdk_version(cwd, &app_dir, app_type, &sdk.package)
+        .and_then(|v| semver::Version::parse(&v).ok());
+    let no_imports = BTreeMap::new();
+    let host = version
+        .and_then(|version| {
+            sdk.versions.iter().find(|v| {
+                semver::VersionReq::parse(&v.range)
+                    .map(|req| req.matches(&version))
+                    .unwrap_or(false)
+            })
+        })
+        .or(sdk.versions.last())
+        .map_or(&no_imports, |v| &v.imports);
+
+    let bytes = fs::read(artifact).context(format!("Failed to read {:?}", artifact))?;
+    let info = wasm::parse(&bytes)?;
+
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    for import in &info.imports {
+        let name = if import.module.is_empty() {
+            import.name.clone()
+        } else {
+            format!("{}.{}", import.module, import.name)
+        };
+
+        if let Some(forbidden) = host_api
+            .forbidden
+            .iter()
+            .find(|f| matches(&f.pattern, &name))
+        {
+            let sources = likely_sources(cwd, &app_dir, app_type, &forbidden.likely_sources);
+            errors.push(if sources.is_empty() {
+                format!("  - {}: {}", name, forbidden.reason)
+            } else {
+                format!(
+                    "  - {}: {}\n      likely pulled in by: {}",
+                    name,
+                    forbidden.reason,
+                    sources.join(", ")
+                )
+            });
+        } else if import.module.is_empty() {
+            if !host.contains_key(interface_name(&import.name)) {
+                warnings.push(format!(
+                    "Import {} is not a known Klave host interface",
+                    name
+                ));
+            }
+        } else if !is_host_function(host, &import.module, &import.name) {
+            warnings.push(format!(
+                "Import {} is not part of the known Klave host API",
+                name
+            ));
+        }
+    }
+
+    if info.kind == WasmKind::Component {
+        for import in info.modules.iter().flat_map(|m| &m.imports) {
+            if !host.contains_key(interface_name(&import.module))
+                || is_host_function(host, &import.module, &import.name)
+            {
+                continue;
+            }
+            let warning = format!(
+                "Import {}.{} is not part of the known Klave host API",
+                import.module, import.name
+            );
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "Artifact imports functions the Klave host does not provide:\n{}",
+            errors.join("\n")
+        ));
+    }
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wit_parser::{ManglingAndAbi, Resolve};
+
+    /// Check an artifact of an app with the marker file of its language
+    fn check(marker: &str, artifact: &[u8]) -> Result<Vec<String>> {
+        let project = tempfile::tempdir().unwrap();
+        let app_dir = project.path().join("apps/hello_world");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join(marker), "").unwrap();
+        let path = project.path().join("hello_world.wasm");
+        fs::write(&path, artifact).unwrap();
+
+        let application = json!({ "slug": "hello-world", "rootDir": "/apps/hello_world" });
+        check_artifact(project.path(), &application, &path)
+    }
+
+    /// Component built for the WIT world of the Rust template
+    fn rust_template_component() -> Vec<u8> {
+        let wit = include_str!("../../templates/rust/apps/hello_world/wit/world.wit")
+            .replace("{{KLAVE_APP_SLUG}}", "hello-world");
+        let mut resolve = Resolve::default();
+        let package = resolve.push_str("world.wit", &wit).unwrap();
+        let world = resolve.select_world(package, None).unwrap();
+
+        let mut module = wit_component::dummy_module(&resolve, world, ManglingAndAbi::Standard32);
+        wit_component::embed_component_metadata(
+            &mut module,
+            &resolve,
+            world,
+            wit_component::StringEncoding::UTF8,
+        )
+        .unwrap();
+        wit_component::ComponentEncoder::default()
+            .module(&module)
+            .unwrap()
+            .validate(true)
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn rust_template_passes() {
+        assert_eq!(
+            check("Cargo.toml", &rust_template_component()).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn assemblyscript_runtime_imports_pass() {
+        let module = wat::parse_str(
+            r#"(module
+                (import "env" "abort" (func (param i32 i32 i32 i32)))
+                (import "env" "seed" (func (result f64))))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            check("tsconfig.json", &module).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn wasi_imports_fail() {
+        let module = wat::parse_str(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32))))"#,
+        )
+        .unwrap();
+        let error = check("tsconfig.json", &module).unwrap_err().to_string();
+        assert!(
+            error.contains("wasi_snapshot_preview1.fd_write"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_interfaces_warn() {
+        let component = wat::parse_str(
+            r#"(component
+                (import "acme:tools/logger" (instance)))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            check("Cargo.toml", &component).unwrap(),
+            ["Import acme:tools/logger is not a known Klave host interface"]
+        );
+    }
+
+    #[test]
+    fn patterns_match_any_sequence() {
+        assert!(matches("wasi:*", "wasi:random/random@0.2.0"));
+        assert!(matches(
+            "wasi_snapshot_preview1.clock_*",
+            "wasi_snapshot_preview1.clock_time_get"
+        ));
+        assert!(!matches("wbg.*", "env.wbg"));
+        assert_eq!(interface_name("wasi:io/streams@0.2.0"), "wasi:io/streams");
+    }
+}
@@ -0,0 +1,358 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::util::project;
+
+/// Variables available to klave.json, from the project `.env` file and the environment
+///
+/// The process environment takes precedence so that CI can override the
+/// values committed to `.env`.
+pub struct Variables {
+    dotenv: BTreeMap<String, String>,
+}
+
+impl Variables {
+    /// Load the `.env` file at the root of the project, if any
+    pub fn load(cwd: &Path) -> Result<Self> {
+        let path = cwd.join(".env");
+        let dotenv = if path.exists() {
+            let content = fs::read_to_string(&path).context("Failed to read .env")?;
+            parse_dotenv(&content)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self { dotenv })
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        env::var(name)
+            .ok()
+            .or_else(|| self.dotenv.get(name).cloned())
+    }
+}
+
+/// What to do with the variable references of a configuration
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Replace them with their values
+    Expand,
+    /// Check they can be resolved but keep them as written, to display a
+    /// configuration without the values of its variables
+    Mask,
+}
+
+/// Parse the `KEY=value` lines of a `.env` file
+///
+/// Blank lines and `#` comments are skipped, an `export ` prefix is allowed
+/// and values may be wrapped in single or double quotes. Double quoted
+/// values understand the `\n`, `\r`, `\t`, `\"` and `\\` escapes, single
+/// quoted values are taken literally.
+fn parse_dotenv(content: &str) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid line {} in .env, expected KEY=value", number + 1))?;
+
+        let value = value.trim();
+        let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            unescape(&value[1..value.len() - 1])
+        } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+            value[1..value.len() - 1].to_string()
+        } else {
+            // Unquoted values may carry a trailing comment
+            value
+                .split(" #")
+                .next()
+                .unwrap_or(value)
+                .trim_end()
+                .to_string()
+        };
+
+        vars.insert(key.trim().to_string(), value);
+    }
+
+    Ok(vars)
+}
+
+/// Replace the escape sequences of a double quoted `.env` value
+///
+/// Unknown sequences are kept as written.
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('t') => output.push('\t'),
+            Some('"') => output.push('"'),
+            Some('\\') => output.push('\\'),
+            Some(other) => {
+                output.push('\\');
+                output.push(other);
+            }
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+/// Expand the `${VAR}` and `${VAR:-default}` references of a string
+///
+/// `$$` produces a literal `$`. As in the shell, the default of
+/// `${VAR:-default}` also replaces an empty value. Undefined variables
+/// without a default are added to `undefined` and left as is.
+fn expand(input: &str, vars: &Variables, undefined: &mut Vec<String>) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+
+        let Some(reference) = rest.strip_prefix("${") else {
+            output.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let end = reference
+            .find('}')
+            .ok_or_else(|| anyhow!("Unterminated variable reference in \"{}\"", input))?;
+        let (name, default) = match reference[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&reference[..end], None),
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!(
+                "Invalid variable name \"{}\" in \"{}\"",
+                name,
+                input
+            ));
+        }
+
+        let value = match (vars.get(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => Some(default.to_string()),
+            (value, default) => value.or_else(|| default.map(str::to_string)),
+        };
+        match value {
+            Some(value) => output.push_str(&value),
+            None => {
+                undefined.push(name.to_string());
+                output.push_str(&rest[..end + 3]);
+            }
+        }
+        rest = &reference[end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Expand variables in every string value of a configuration
+fn walk(
+    value: &mut Value,
+    path: String,
+    vars: &Variables,
+    mode: Mode,
+    undefined: &mut BTreeMap<String, Vec<String>>,
+) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            let mut names = Vec::new();
+            let expanded = expand(s, vars, &mut names)?;
+            if mode == Mode::Expand {
+                *s = expanded;
+            }
+            for name in names {
+                undefined.entry(name).or_default().push(path.clone());
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                let path = if path == "applications" {
+                    format!("applications[{}]", project::app_slug(item))
+                } else {
+                    format!("{}[{}]", path, index)
+                };
+                walk(item, path, vars, mode, undefined)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                walk(item, path, vars, mode, undefined)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Resolve the variable references of a klave.json configuration
+///
+/// Every undefined variable is reported at once, with the keys using it.
+pub fn resolve(cwd: &Path, config: &mut Value, mode: Mode) -> Result<()> {
+    let vars = Variables::load(cwd)?;
+    let mut undefined = BTreeMap::new();
+    walk(config, String::new(), &vars, mode, &mut undefined)?;
+
+    if !undefined.is_empty() {
+        let list: Vec<String> = undefined
+            .iter()
+            .map(|(name, paths)| format!("  {} (used by {})", name, paths.join(", ")))
+            .collect();
+        return Err(anyhow!(
+            "Undefined variables in klave.json, set them in the environment or in .env, or give a default with ${{VAR:-default}}:\n{}",
+            list.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Variables from a `.env`, under names not set in the test environment
+    fn variables(dotenv: &str) -> Variables {
+        Variables {
+            dotenv: parse_dotenv(dotenv).unwrap(),
+        }
+    }
+
+    fn expanded(input: &str, vars: &Variables) -> (String, Vec<String>) {
+        let mut undefined = Vec::new();
+        let output = expand(input, vars, &mut undefined).unwrap();
+        (output, undefined)
+    }
+
+    #[test]
+    fn parse_dotenv_reads_quotes_comments_and_escapes() {
+        let vars = parse_dotenv(
+            "# comment\n\
+             \n\
+             export KLAVE_T_PLAIN = value # trailing comment\n\
+             KLAVE_T_DOUBLE=\"line one\\nline \\\"two\\\"\\t\\\\ \\x #kept\"\n\
+             KLAVE_T_SINGLE='literal \\n $HOME'\n\
+             KLAVE_T_EMPTY=\n\
+             KLAVE_T_URL=https://example.com/#anchor\n",
+        )
+        .unwrap();
+
+        assert_eq!(vars["KLAVE_T_PLAIN"], "value");
+        assert_eq!(
+            vars["KLAVE_T_DOUBLE"],
+            "line one\nline \"two\"\t\\ \\x #kept"
+        );
+        assert_eq!(vars["KLAVE_T_SINGLE"], "literal \\n $HOME");
+        assert_eq!(vars["KLAVE_T_EMPTY"], "");
+        assert_eq!(vars["KLAVE_T_URL"], "https://example.com/#anchor");
+
+        let error = parse_dotenv("KLAVE_T_OK=1\nnot a variable\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid line 2 in .env, expected KEY=value"
+        );
+    }
+
+    #[test]
+    fn expand_replaces_references_and_defaults() {
+        let vars = variables("KLAVE_T_NAME=hello\nKLAVE_T_EMPTY=\n");
+
+        assert_eq!(
+            expanded("${KLAVE_T_NAME}-${KLAVE_T_NAME:-other}", &vars).0,
+            "hello-hello"
+        );
+        assert_eq!(expanded("${KLAVE_T_UNSET:-fallback}", &vars).0, "fallback");
+        assert_eq!(expanded("${KLAVE_T_EMPTY:-fallback}", &vars).0, "fallback");
+        assert_eq!(expanded("[${KLAVE_T_EMPTY}]", &vars).0, "[]");
+        assert_eq!(expanded("${KLAVE_T_UNSET:-}", &vars).0, "");
+        assert_eq!(expanded("no references", &vars).0, "no references");
+    }
+
+    #[test]
+    fn expand_keeps_dollars_that_are_not_references() {
+        let vars = variables("KLAVE_T_NAME=hello\n");
+
+        assert_eq!(expanded("$$", &vars).0, "$");
+        assert_eq!(expanded("$${KLAVE_T_NAME}", &vars).0, "${KLAVE_T_NAME}");
+        assert_eq!(expanded("$$${KLAVE_T_NAME}", &vars).0, "$hello");
+        assert_eq!(expanded("$$$${KLAVE_T_NAME}", &vars).0, "$${KLAVE_T_NAME}");
+        assert_eq!(expanded("cost $5 $", &vars).0, "cost $5 $");
+        assert_eq!(expanded("$KLAVE_T_NAME", &vars).0, "$KLAVE_T_NAME");
+    }
+
+    #[test]
+    fn expand_reports_undefined_and_invalid_references() {
+        let vars = variables("");
+
+        let (output, undefined) = expanded("a ${KLAVE_T_UNSET} b", &vars);
+        assert_eq!(output, "a ${KLAVE_T_UNSET} b");
+        assert_eq!(undefined, ["KLAVE_T_UNSET"]);
+
+        let mut undefined = Vec::new();
+        assert!(expand("${KLAVE_T_UNSET", &vars, &mut undefined).is_err());
+        assert!(expand("${}", &vars, &mut undefined).is_err());
+        assert!(expand("${NOT-VALID}", &vars, &mut undefined).is_err());
+    }
+
+    #[test]
+    fn resolve_lists_every_undefined_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "KLAVE_T_NAME=hello\n").unwrap();
+        let mut config = json!({
+            "name": "${KLAVE_T_NAME}",
+            "applications": [{
+                "slug": "hello",
+                "url": "${KLAVE_T_HOST}/${KLAVE_T_PATH}",
+                "tags": ["${KLAVE_T_HOST}"]
+            }]
+        });
+
+        let error = resolve(dir.path(), &mut config.clone(), Mode::Mask)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.ends_with(
+            "\n  KLAVE_T_HOST (used by applications[hello].tags[0], applications[hello].url)\
+             \n  KLAVE_T_PATH (used by applications[hello].url)"
+        ));
+
+        config["applications"][0] = json!({ "slug": "hello" });
+        resolve(dir.path(), &mut config, Mode::Expand).unwrap();
+        assert_eq!(config["name"], "hello");
+    }
+}
@@ -0,0 +1,683 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, anyhow};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use colored::*;
+use dialoguer::Password;
+use dialoguer::theme::ColorfulTheme;
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::{
+    DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding,
+};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::util::config;
+
+/// Name of the key used when none is specified
+pub const DEFAULT_KEY: &str = "default";
+
+/// Formats public keys can be printed in
+pub const PUBLIC_KEY_FORMATS: [&str; 4] = ["base64", "pem", "jwk", "hex"];
+
+/// Formats private keys can be exported in
+pub const PRIVATE_KEY_FORMATS: [&str; 2] = ["pem", "jwk"];
+
+/// Run `$body` with `$module` bound to the crate implementing `$curve`
+macro_rules! with_curve {
+    ($curve:expr, $module:ident => $body:expr) => {
+        match $curve {
+            Curve::Secp256r1 => {
+                use ::p256 as $module;
+                $body
+            }
+            Curve::Secp384r1 => {
+                use ::p384 as $module;
+                $body
+            }
+            Curve::Secp256k1 => {
+                use ::k256 as $module;
+                $body
+            }
+        }
+    };
+}
+
+/// Elliptic curves used by Klave for identities and request signatures
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Curve {
+    Secp256r1,
+    Secp384r1,
+    Secp256k1,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 3] = [Curve::Secp256r1, Curve::Secp384r1, Curve::Secp256k1];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::Secp256r1 => "secp256r1",
+            Curve::Secp384r1 => "secp384r1",
+            Curve::Secp256k1 => "secp256k1",
+        }
+    }
+
+    /// Name of the curve in JSON Web Keys
+    fn jwk_name(&self) -> &'static str {
+        match self {
+            Curve::Secp256r1 => "P-256",
+            Curve::Secp384r1 => "P-384",
+            Curve::Secp256k1 => "secp256k1",
+        }
+    }
+
+    /// Signature algorithm of keys on this curve
+    pub fn signature_algorithm(&self) -> &'static str {
+        match self {
+            Curve::Secp256r1 => "ecdsa-p256-sha256",
+            Curve::Secp384r1 => "ecdsa-p384-sha384",
+            Curve::Secp256k1 => "ecdsa-k256-sha256",
+        }
+    }
+
+    pub fn from_signature_algorithm(algorithm: &str) -> Option<Curve> {
+        Curve::ALL
+            .into_iter()
+            .find(|curve| curve.signature_algorithm() == algorithm)
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Curve {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "secp256r1" | "p256" | "p-256" | "prime256v1" => Ok(Curve::Secp256r1),
+            "secp384r1" | "p384" | "p-384" => Ok(Curve::Secp384r1),
+            "secp256k1" | "k256" => Ok(Curve::Secp256k1),
+            _ => Err(anyhow!(
+                "Unsupported curve \"{}\", expected one of: {}",
+                name,
+                Curve::ALL.map(|c| c.name()).join(", ")
+            )),
+        }
+    }
+}
+
+fn keys_dir() -> Result<PathBuf> {
+    Ok(config::config_dir()?.join("keys"))
+}
+
+fn key_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        || name.starts_with('.')
+    {
+        return Err(anyhow!(
+            "Invalid key name \"{}\", use letters, digits, '-', '_' and '.'",
+            name
+        ));
+    }
+
+    Ok(keys_dir()?.join(format!("{}.json", name)))
+}
+
+/// Parameters used to encrypt a private key with a passphrase
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Encryption {
+    kdf: String,
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+}
+
+/// On-disk representation of a developer key
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    name: String,
+    #[serde(default = "default_curve")]
+    curve: String,
+    /// PKCS#8 DER private key, base64 encoded and encrypted when `encryption` is set
+    private_key: String,
+    /// SPKI DER public key, base64 encoded
+    public_key: String,
+    #[serde(default)]
+    encryption: Option<Encryption>,
+    created_at: u64,
+}
+
+fn default_curve() -> String {
+    Curve::Secp256r1.name().to_string()
+}
+
+/// Public details of a stored key
+pub struct KeyInfo {
+    pub name: String,
+    pub curve: Curve,
+    /// SPKI DER public key, base64 encoded
+    pub public_key: String,
+    pub encrypted: bool,
+    pub path: PathBuf,
+}
+
+fn read_key_file(path: &Path) -> Result<KeyFile> {
+    config::check_private_file(path)?;
+    serde_json::from_str(&fs::read_to_string(path).context(format!("Failed to read {:?}", path))?)
+        .context(format!("Invalid key file {:?}", path))
+}
+
+fn key_info(key_file: &KeyFile, path: &Path) -> Result<KeyInfo> {
+    Ok(KeyInfo {
+        name: key_file.name.clone(),
+        curve: key_file.curve.parse()?,
+        public_key: key_file.public_key.clone(),
+        encrypted: key_file.encryption.is_some(),
+        path: path.to_path_buf(),
+    })
+}
+
+/// Passphrase protecting the key store, from `KLAVE_KEY_PASSPHRASE` or a prompt
+fn passphrase(prompt: &str, confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = env::var("KLAVE_KEY_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+
+    let theme = ColorfulTheme::default();
+    let mut input = Password::with_theme(&theme).with_prompt(prompt);
+    if confirm {
+        input = input.with_confirmation("Repeat the passphrase", "Passphrases do not match");
+    }
+
+    input
+        .interact()
+        .context("Failed to read passphrase, set KLAVE_KEY_PASSPHRASE in non-interactive shells")
+}
+
+fn derive_key(passphrase: &str, encryption: &Encryption) -> Result<Aes256Gcm> {
+    let params = Params::new(
+        encryption.memory_cost,
+        encryption.time_cost,
+        encryption.parallelism,
+        Some(32),
+    )
+    .map_err(|e| anyhow!("Invalid key derivation parameters: {}", e))?;
+
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(
+            passphrase.as_bytes(),
+            &BASE64.decode(&encryption.salt)?,
+            &mut key,
+        )
+        .map_err(|e| anyhow!("Failed to derive encryption key: {}", e))?;
+
+    Ok(Aes256Gcm::new(&key.into()))
+}
+
+fn encrypt(passphrase: &str, plaintext: &[u8]) -> Result<(Encryption, String)> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let params = Params::default();
+    let encryption = Encryption {
+        kdf: "argon2id".to_string(),
+        memory_cost: params.m_cost(),
+        time_cost: params.t_cost(),
+        parallelism: params.p_cost(),
+        salt: BASE64.encode(salt),
+        cipher: "aes-256-gcm".to_string(),
+        nonce: BASE64.encode(nonce),
+    };
+
+    let ciphertext = derive_key(passphrase, &encryption)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt private key"))?;
+
+    Ok((encryption, BASE64.encode(ciphertext)))
+}
+
+fn decrypt(key_file: &KeyFile) -> Result<Vec<u8>> {
+    decrypt_with(key_file, || {
+        passphrase(&format!("Passphrase for key \"{}\"", key_file.name), false)
+    })
+}
+
+/// Decrypt a private key, asking for the passphrase only if it is encrypted
+fn decrypt_with(
+    key_file: &KeyFile,
+    passphrase: impl FnOnce() -> Result<String>,
+) -> Result<Vec<u8>> {
+    let private_key = BASE64
+        .decode(&key_file.private_key)
+        .context("Invalid private key encoding")?;
+
+    let Some(encryption) = &key_file.encryption else {
+        return Ok(private_key);
+    };
+
+    if encryption.kdf != "argon2id" || encryption.cipher != "aes-256-gcm" {
+        return Err(anyhow!(
+            "Unsupported key encryption {}/{}",
+            encryption.kdf,
+            encryption.cipher
+        ));
+    }
+
+    derive_key(&passphrase()?, encryption)?
+        .decrypt(
+            Nonce::from_slice(&BASE64.decode(&encryption.nonce)?),
+            private_key.as_slice(),
+        )
+        .map_err(|_| anyhow!("Wrong passphrase for key \"{}\"", key_file.name))
+}
+
+/// A developer key used to sign build manifests and requests
+pub struct DeveloperKey {
+    pub name: String,
+    pub curve: Curve,
+    /// PKCS#8 DER private key
+    private_key: Vec<u8>,
+}
+
+impl DeveloperKey {
+    fn new(name: &str, curve: Curve, private_key: Vec<u8>) -> Result<Self> {
+        with_curve!(curve, c => {
+            c::SecretKey::from_pkcs8_der(&private_key)
+                .map_err(|e| anyhow!("Invalid {} private key: {}", curve, e))?;
+        });
+
+        Ok(DeveloperKey {
+            name: name.to_string(),
+            curve,
+            private_key,
+        })
+    }
+
+    fn generate(name: &str, curve: Curve) -> Result<Self> {
+        let private_key = with_curve!(curve, c => c::SecretKey::random(&mut OsRng)
+            .to_pkcs8_der()
+            .map_err(|e| anyhow!("Failed to encode private key: {}", e))?
+            .as_bytes()
+            .to_vec());
+
+        Self::new(name, curve, private_key)
+    }
+
+    /// Public key as base64 encoded SPKI DER
+    pub fn public_key(&self) -> Result<String> {
+        let der = with_curve!(self.curve, c => c::SecretKey::from_pkcs8_der(&self.private_key)?
+            .public_key()
+            .to_public_key_der()
+            .map_err(|e| anyhow!("Failed to encode public key: {}", e))?
+            .into_vec());
+
+        Ok(BASE64.encode(der))
+    }
+
+    /// Sign a message, returning a base64 encoded DER signature
+    pub fn sign(&self, message: &[u8]) -> Result<String> {
+        let der = with_curve!(self.curve, c => {
+            let secret = c::SecretKey::from_pkcs8_der(&self.private_key)?;
+            let signature: c::ecdsa::Signature = c::ecdsa::SigningKey::from(secret).sign(message);
+            signature.to_der().as_bytes().to_vec()
+        });
+
+        Ok(BASE64.encode(der))
+    }
+
+    /// Private key in one of [`PRIVATE_KEY_FORMATS`]
+    pub fn export_private(&self, format: &str) -> Result<String> {
+        with_curve!(self.curve, c => {
+            let secret = c::SecretKey::from_pkcs8_der(&self.private_key)?;
+            match format {
+                "pem" => Ok(secret
+                    .to_pkcs8_pem(LineEnding::LF)
+                    .map_err(|e| anyhow!("Failed to encode private key: {}", e))?
+                    .to_string()),
+                "jwk" => Ok(secret.to_jwk_string().to_string()),
+                _ => Err(anyhow!(
+                    "Unsupported private key format \"{}\", expected one of: {}",
+                    format,
+                    PRIVATE_KEY_FORMATS.join(", ")
+                )),
+            }
+        })
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let passphrase = passphrase(
+            &format!("Passphrase to protect key \"{}\"", self.name),
+            true,
+        )?;
+        if passphrase.is_empty() {
+            return Err(anyhow!("The passphrase cannot be empty"));
+        }
+
+        let (encryption, private_key) = encrypt(&passphrase, &self.private_key)?;
+        let key_file = KeyFile {
+            name: self.name.clone(),
+            curve: self.curve.name().to_string(),
+            private_key,
+            public_key: self.public_key()?,
+            encryption: Some(encryption),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+
+        config::write_private_file(path, serde_json::to_string_pretty(&key_file)?.as_bytes())
+    }
+}
+
+/// Format a base64 SPKI public key in one of [`PUBLIC_KEY_FORMATS`]
+///
+/// `base64` is the SPKI encoding WebCrypto exports and the Klave web UI
+/// displays, `hex` the raw uncompressed point.
+pub fn format_public_key(curve: Curve, public_key: &str, format: &str) -> Result<String> {
+    let der = BASE64
+        .decode(public_key)
+        .context("Invalid public key encoding")?;
+
+    with_curve!(curve, c => {
+        let key = c::PublicKey::from_public_key_der(&der)
+            .map_err(|e| anyhow!("Invalid {} public key: {}", curve, e))?;
+        match format {
+            "base64" => Ok(public_key.to_string()),
+            "pem" => key
+                .to_public_key_pem(LineEnding::LF)
+                .map_err(|e| anyhow!("Failed to encode public key: {}", e)),
+            "jwk" => Ok(key.to_jwk_string()),
+            "hex" => Ok(key
+                .to_encoded_point(false)
+                .as_bytes()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect()),
+            _ => Err(anyhow!(
+                "Unsupported public key format \"{}\", expected one of: {}",
+                format,
+                PUBLIC_KEY_FORMATS.join(", ")
+            )),
+        }
+    })
+}
+
+/// List the keys in the key store, sorted by name
+///
+/// Key files that cannot be read are skipped with a warning, so one bad file
+/// does not hide the other keys.
+pub fn list() -> Result<Vec<KeyInfo>> {
+    list_in(&keys_dir()?)
+}
+
+fn list_in(dir: &Path) -> Result<Vec<KeyInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            match read_key_file(&path).and_then(|key_file| key_info(&key_file, &path)) {
+                Ok(key) => keys.push(key),
+                Err(e) => eprintln!(
+                    "{}",
+                    format!("Warning: Skipping key file: {:#}", e).yellow()
+                ),
+            }
+        }
+    }
+
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(keys)
+}
+
+/// Public details of a stored key
+pub fn info(name: &str) -> Result<KeyInfo> {
+    let path = key_path(name)?;
+    if !path.exists() {
+        return Err(anyhow!(
+            "No key named \"{}\", create one with: klave keys generate {}",
+            name,
+            name
+        ));
+    }
+
+    key_info(&read_key_file(&path)?, &path)
+}
+
+/// Generate a key and add it to the key store
+pub fn generate(name: &str, curve: Curve) -> Result<KeyInfo> {
+    let path = key_path(name)?;
+    if path.exists() {
+        return Err(anyhow!("A key named \"{}\" already exists", name));
+    }
+
+    DeveloperKey::generate(name, curve)?.save(&path)?;
+    info(name)
+}
+
+/// Import a private key given as PKCS#8 PEM, base64 PKCS#8 DER or JWK
+pub fn import(name: &str, contents: &str) -> Result<KeyInfo> {
+    let path = key_path(name)?;
+    if path.exists() {
+        return Err(anyhow!("A key named \"{}\" already exists", name));
+    }
+
+    parse_private_key(name, contents)?.save(&path)?;
+    info(name)
+}
+
+fn parse_private_key(name: &str, contents: &str) -> Result<DeveloperKey> {
+    let contents = contents.trim();
+    Curve::ALL
+        .into_iter()
+        .find_map(|curve| {
+            let der = with_curve!(curve, c => {
+                let secret = if contents.starts_with("-----BEGIN") {
+                    c::SecretKey::from_pkcs8_pem(contents).ok()?
+                } else if contents.starts_with('{') {
+                    let jwk: serde_json::Value = serde_json::from_str(contents).ok()?;
+                    if jwk.get("crv")?.as_str()? != curve.jwk_name() {
+                        return None;
+                    }
+                    c::SecretKey::from_jwk_str(contents).ok()?
+                } else {
+                    c::SecretKey::from_pkcs8_der(&BASE64.decode(contents).ok()?).ok()?
+                };
+                secret.to_pkcs8_der().ok()?.as_bytes().to_vec()
+            });
+            DeveloperKey::new(name, curve, der).ok()
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not read a private key on any of the supported curves ({})",
+                Curve::ALL.map(|c| c.name()).join(", ")
+            )
+        })
+}
+
+/// Load and decrypt a key from the key store
+pub fn load(name: &str) -> Result<DeveloperKey> {
+    let path = info(name)?.path;
+    let key_file = read_key_file(&path)?;
+    DeveloperKey::new(name, key_file.curve.parse()?, decrypt(&key_file)?)
+}
+
+/// Load a developer key, generating the default key on first use
+pub fn load_or_create(name: &str) -> Result<DeveloperKey> {
+    if name == DEFAULT_KEY && !key_path(name)?.exists() {
+        let key = generate(name, Curve::Secp256r1)?;
+        println!("Generated developer key \"{}\" in {:?}", key.name, key.path);
+    }
+
+    load(name)
+}
+
+/// Remove a key from the key store
+pub fn delete(name: &str) -> Result<()> {
+    let path = info(name)?.path;
+    fs::remove_file(&path).context(format!("Failed to delete {:?}", path))
+}
+
+/// Verify a base64 DER signature against a base64 SPKI DER public key
+pub fn verify(algorithm: &str, public_key: &str, message: &[u8], signature: &str) -> Result<()> {
+    let curve = Curve::from_signature_algorithm(algorithm)
+        .ok_or_else(|| anyhow!("Unsupported signature algorithm \"{}\"", algorithm))?;
+    let der = BASE64
+        .decode(public_key)
+        .context("Invalid public key encoding")?;
+    let signature = BASE64
+        .decode(signature)
+        .context("Invalid signature encoding")?;
+
+    with_curve!(curve, c => {
+        let verifying_key = c::ecdsa::VerifyingKey::from_public_key_der(&der)
+            .map_err(|e| anyhow!("Invalid public key: {}", e))?;
+        let signature = c::ecdsa::Signature::from_der(&signature)
+            .map_err(|e| anyhow!("Invalid signature: {}", e))?;
+        verifying_key
+            .verify(message, &signature)
+            .map_err(|_| anyhow!("Signature does not match"))
+    })
+}
+
+/// Short fingerprint of a public key, the start of the SHA-256 of its encoding
+pub fn fingerprint(public_key: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(public_key.as_bytes()));
+    digest[..16].to_string()
+}
+
+/// Find the name of a local key by its public key
+pub fn find_by_public_key(public_key: &str) -> Option<String> {
+    list()
+        .ok()?
+        .into_iter()
+        .find(|key| key.public_key == public_key)
+        .map(|key| key.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypted_key_file(passphrase: &str, private_key: &[u8]) -> KeyFile {
+        let (encryption, private_key) = encrypt(passphrase, private_key).unwrap();
+        KeyFile {
+            name: "test".to_string(),
+            curve: Curve::Secp256r1.name().to_string(),
+            private_key,
+            public_key: String::new(),
+            encryption: Some(encryption),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn passphrase_round_trip() {
+        let key_file = encrypted_key_file("correct horse", b"private key bytes");
+        assert_ne!(key_file.private_key, BASE64.encode(b"private key bytes"));
+
+        let decrypted = decrypt_with(&key_file, || Ok("correct horse".to_string())).unwrap();
+        assert_eq!(decrypted, b"private key bytes");
+    }
+
+    #[test]
+    fn wrong_passphrase_is_rejected() {
+        let key_file = encrypted_key_file("correct horse", b"private key bytes");
+
+        let error = decrypt_with(&key_file, || Ok("battery staple".to_string())).unwrap_err();
+        assert_eq!(error.to_string(), "Wrong passphrase for key \"test\"");
+    }
+
+    #[test]
+    fn unencrypted_keys_do_not_ask_for_a_passphrase() {
+        let key_file = KeyFile {
+            encryption: None,
+            private_key: BASE64.encode(b"private key bytes"),
+            ..encrypted_key_file("unused", b"")
+        };
+
+        let decrypted = decrypt_with(&key_file, || panic!("asked for a passphrase")).unwrap();
+        assert_eq!(decrypted, b"private key bytes");
+    }
+
+    #[test]
+    fn imports_every_format_on_every_curve() {
+        for curve in Curve::ALL {
+            let key = DeveloperKey::generate("original", curve).unwrap();
+            let formats = [
+                ("pem", key.export_private("pem").unwrap()),
+                ("base64 der", BASE64.encode(&key.private_key)),
+                ("jwk", key.export_private("jwk").unwrap()),
+            ];
+
+            for (format, contents) in formats {
+                let imported = parse_private_key("imported", &format!("\n{}\n", contents))
+                    .unwrap_or_else(|e| panic!("{} {}: {}", curve, format, e));
+                assert_eq!(imported.curve, curve, "{} {}", curve, format);
+                assert_eq!(
+                    imported.public_key().unwrap(),
+                    key.public_key().unwrap(),
+                    "{} {}",
+                    curve,
+                    format
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unreadable_private_keys() {
+        assert!(parse_private_key("bad", "not a key").is_err());
+        assert!(parse_private_key("bad", "{\"kty\":\"EC\",\"crv\":\"P-521\"}").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_skips_key_files_other_users_can_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let good = encrypted_key_file("pass", b"");
+        config::write_private_file(
+            &dir.path().join("good.json"),
+            serde_json::to_string(&good).unwrap().as_bytes(),
+        )
+        .unwrap();
+        let shared = dir.path().join("shared.json");
+        fs::write(&shared, serde_json::to_string(&good).unwrap()).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let keys = list_in(dir.path()).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].path, dir.path().join("good.json"));
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::util::keys::{self, DeveloperKey};
+use crate::util::{project, provenance, wasm};
+
+/// An artifact listed in a build manifest
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestArtifact {
+    pub slug: String,
+    pub version: String,
+    /// Path of the artifact relative to the manifest
+    pub file: String,
+    pub sha256: String,
+    pub size: usize,
+    pub provenance: Option<Value>,
+}
+
+/// Another file covered by a manifest, such as a bundled klave.json
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    /// Path of the file relative to the manifest
+    pub file: String,
+    pub sha256: String,
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestSignature {
+    pub algorithm: String,
+    pub key_name: String,
+    pub public_key: String,
+    pub value: String,
+}
+
+/// Build manifest listing artifacts, signed with a developer key
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub version: u32,
+    pub created_at: u64,
+    pub artifacts: Vec<ManifestArtifact>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ManifestFile>,
+    pub signature: Option<ManifestSignature>,
+}
+
+pub fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Bytes covered by the signature: the manifest without its signature, with
+/// object keys sorted so the encoding is stable across serialisations.
+fn signed_bytes(manifest: &Manifest) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(manifest)?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove("signature");
+    }
+    Ok(serde_json::to_vec(&value)?)
+}
+
+/// Describe an application's artifact, copying it into `dir`
+pub fn add_artifact(cwd: &Path, application: &Value, dir: &Path) -> Result<ManifestArtifact> {
+    let artifact = project::artifact_path(cwd, application)?;
+    let bytes = fs::read(&artifact).context(format!("Failed to read {:?}", artifact))?;
+
+    let slug = project::app_slug(application).to_string();
+    let file = format!("{}.wasm", slug);
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(&file), &bytes)?;
+
+    let provenance = match provenance::read(&wasm::parse(&bytes)?) {
+        Some(provenance) => Some(serde_json::to_value(provenance?)?),
+        None => None,
+    };
+
+    Ok(ManifestArtifact {
+        version: application
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        slug,
+        file,
+        sha256: sha256(&bytes),
+        size: bytes.len(),
+        provenance,
+    })
+}
+
+/// Describe a file of `dir` to be covered by a manifest
+pub fn add_file(dir: &Path, file: &str) -> Result<ManifestFile> {
+    let path = dir.join(file);
+    let bytes = fs::read(&path).context(format!("Failed to read {:?}", path))?;
+
+    Ok(ManifestFile {
+        file: file.to_string(),
+        sha256: sha256(&bytes),
+        size: bytes.len(),
+    })
+}
+
+/// Write a manifest as `manifest.json` in `dir`, signed when a key is given
+pub fn write(
+    dir: &Path,
+    artifacts: Vec<ManifestArtifact>,
+    files: Vec<ManifestFile>,
+    key: Option<&DeveloperKey>,
+) -> Result<PathBuf> {
+    let mut manifest = Manifest {
+        version: 1,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        artifacts,
+        files,
+        signature: None,
+    };
+
+    if let Some(key) = key {
+        let signature = key.sign(&signed_bytes(&manifest)?)?;
+        manifest.signature = Some(ManifestSignature {
+            algorithm: key.curve.signature_algorithm().to_string(),
+            key_name: key.name.clone(),
+            public_key: key.public_key()?,
+            value: signature,
+        });
+    }
+
+    let path = dir.join("manifest.json");
+    fs::create_dir_all(dir)?;
+    fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+        .context(format!("Failed to write {:?}", path))?;
+
+    Ok(path)
+}
+
+/// Read a manifest file
+pub fn read(path: &Path) -> Result<Manifest> {
+    serde_json::from_str(&fs::read_to_string(path).context(format!("Failed to read {:?}", path))?)
+        .context(format!("Invalid manifest {:?}", path))
+}
+
+/// Check a file listed in a manifest still has the recorded hash
+///
+/// Paths must stay inside `dir`, so a manifest cannot point at other files.
+pub fn check_file(dir: &Path, file: &str, expected: &str) -> std::result::Result<(), String> {
+    let inside = Path::new(file)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if !inside {
+        return Err(format!("invalid path {}", file));
+    }
+
+    let path = dir.join(file);
+    match fs::read(&path) {
+        Ok(bytes) if sha256(&bytes) == expected => Ok(()),
+        Ok(bytes) => Err(format!("hash mismatch, found {}", sha256(&bytes))),
+        Err(error) => Err(format!("cannot read {:?}: {}", path, error)),
+    }
+}
+
+/// Check the signature of a manifest
+pub fn verify_signature(manifest: &Manifest) -> Result<&ManifestSignature> {
+    let signature = manifest
+        .signature
+        .as_ref()
+        .ok_or_else(|| anyhow!("Manifest is not signed"))?;
+
+    keys::verify(
+        &signature.algorithm,
+        &signature.public_key,
+        &signed_bytes(manifest)?,
+        &signature.value,
+    )?;
+
+    Ok(signature)
+}
+
+/// Why the key of a manifest signature is trusted
+pub enum Trust {
+    /// It is the key given on the command line
+    Expected,
+    /// It is one of the local keys, by name
+    LocalKey(String),
+}
+
+/// Check the signature of a manifest and that it was made with a trusted key
+///
+/// A key given as `public_key` is the only one trusted; otherwise the key
+/// must be one of the local keys. A valid signature from any other key proves
+/// nothing, since anyone can sign with a key of their own.
+pub fn verify_trusted<'a>(
+    manifest: &'a Manifest,
+    public_key: Option<&str>,
+) -> Result<(&'a ManifestSignature, Trust)> {
+    let signature = verify_signature(manifest)?;
+    let fingerprint = keys::fingerprint(&signature.public_key);
+
+    match public_key.map(str::trim) {
+        Some(expected) if expected == signature.public_key => Ok((signature, Trust::Expected)),
+        Some(expected) => Err(anyhow!(
+            "Manifest is signed by key {} which is not the expected key {}",
+            fingerprint,
+            keys::fingerprint(expected)
+        )),
+        None => match keys::find_by_public_key(&signature.public_key) {
+            Some(name) => Ok((signature, Trust::LocalKey(name))),
+            None => Err(anyhow!(
+                "Manifest is signed by key {} which is not one of your local keys. Check its fingerprint with the author and pass their public key with --public-key",
+                fingerprint
+            )),
+        },
+    }
+}
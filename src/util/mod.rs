@@ -1,2 +1,20 @@
 // Declare all command modules
+pub mod api;
+pub mod branches;
+pub mod bundle;
+pub mod config;
+pub mod credentials;
+pub mod environment;
+#[allow(dead_code)]
+#[rustfmt::skip]
+pub mod git;
+pub mod host_api;
+pub mod interpolate;
+pub mod keys;
+pub mod manifest;
+pub mod optimize;
+pub mod project;
+pub mod provenance;
+pub mod secrets;
 pub mod template;
+pub mod wasm;
@@ -0,0 +1,196 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::util::project;
+use crate::util::wasm;
+
+/// Optimisation levels understood by binaryen's wasm-opt
+const LEVELS: [&str; 7] = ["0", "1", "2", "3", "4", "s", "z"];
+
+/// Optimisation settings for an application
+pub struct OptimizeOptions {
+    /// wasm-opt optimisation level, one of `LEVELS`
+    pub level: String,
+    /// Strip debug information, names and source maps
+    pub strip: bool,
+}
+
+/// Outcome of an optimisation pass
+pub struct OptimizeReport {
+    pub before: usize,
+    pub after: usize,
+    /// Set when wasm-opt was not applied
+    pub skipped_reason: Option<String>,
+    /// Whether debug sections were stripped
+    pub stripped: bool,
+}
+
+/// Normalise a level given as `z`, `Oz`, `-Oz` or `2`
+fn parse_level(level: &str) -> Result<String> {
+    let normalized = level.trim_start_matches('-').trim_start_matches('O');
+    if LEVELS.contains(&normalized) {
+        Ok(normalized.to_string())
+    } else {
+        Err(anyhow!(
+            "Invalid optimisation level \"{}\". Expected one of: {}",
+            level,
+            LEVELS.map(|l| format!("O{}", l)).join(", ")
+        ))
+    }
+}
+
+/// Resolve the optimisation settings of an application
+///
+/// The `optimize` entry in klave.json may be `true`, a level such as `"Oz"`, or
+/// an object `{ "level": "Oz", "strip": true }`. A level given on the command
+/// line enables optimisation for every app and takes precedence.
+pub fn options_for(
+    application: &Value,
+    cli_level: Option<&str>,
+) -> Result<Option<OptimizeOptions>> {
+    let (level, strip) = match application.get("optimize") {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => (None, true),
+        Some(Value::Bool(true)) => (Some("s".to_string()), true),
+        Some(Value::String(level)) => (Some(level.clone()), true),
+        Some(Value::Object(settings)) => (
+            Some(
+                settings
+                    .get("level")
+                    .and_then(|l| l.as_str())
+                    .unwrap_or("s")
+                    .to_string(),
+            ),
+            settings
+                .get("strip")
+                .and_then(|s| s.as_bool())
+                .unwrap_or(true),
+        ),
+        Some(other) => {
+            return Err(anyhow!(
+                "Invalid optimize setting {} for \"{}\"",
+                other,
+                project::app_slug(application)
+            ));
+        }
+    };
+
+    match cli_level.map(str::to_string).or(level) {
+        Some(level) => Ok(Some(OptimizeOptions {
+            level: parse_level(&level)?,
+            strip,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Whether a custom section only carries debug information
+fn is_debug_section(name: &str) -> bool {
+    name == "name"
+        || name == "component-name"
+        || name == "sourceMappingURL"
+        || name == "external_debug_info"
+        || name.starts_with(".debug_")
+}
+
+/// Export names that must survive optimisation
+fn export_names(bytes: &[u8]) -> Result<BTreeSet<String>> {
+    Ok(wasm::parse(bytes)?
+        .exports
+        .into_iter()
+        .map(|e| e.name)
+        .collect())
+}
+
+/// Check an optimised module or component exports the same names as the original
+fn check_exports(original: &[u8], optimized: &[u8], what: &str) -> Result<()> {
+    let before_exports = export_names(original)?;
+    let after_exports = export_names(optimized)?;
+    if before_exports != after_exports {
+        let changed: Vec<&str> = before_exports
+            .symmetric_difference(&after_exports)
+            .map(|s| s.as_str())
+            .collect();
+        return Err(anyhow!(
+            "Optimised {} changed its exports: {}",
+            what,
+            changed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Run wasm-opt on a core module
+fn run_wasm_opt(bytes: &[u8], level: &str) -> Result<Vec<u8>> {
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("input.wasm");
+    let output = dir.path().join("output.wasm");
+    fs::write(&input, bytes)?;
+
+    let result = Command::new("wasm-opt")
+        .arg(&input)
+        .arg(format!("-O{}", level))
+        .arg("--dce")
+        .arg("--all-features")
+        .arg("-o")
+        .arg(&output)
+        .output()
+        .context("Failed to run wasm-opt")?;
+
+    if !result.status.success() {
+        return Err(anyhow!(
+            "wasm-opt failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+
+    fs::read(&output).context("Failed to read wasm-opt output")
+}
+
+/// Optimise an artifact in place
+///
+/// wasm-opt runs on each core module, those nested in a component included,
+/// and every optimised module must export the same names as the original.
+/// The optimised binary is then validated and must export the same names
+/// before it replaces the artifact; otherwise the artifact is left untouched
+/// and an error is returned.
+pub fn optimize_artifact(artifact: &Path, options: &OptimizeOptions) -> Result<OptimizeReport> {
+    let original = fs::read(artifact).context(format!("Failed to read {:?}", artifact))?;
+
+    let mut skipped_reason = None;
+    let mut optimized = if Command::new("wasm-opt").arg("--version").output().is_err() {
+        skipped_reason = Some(
+            "wasm-opt not found, install binaryen from https://github.com/WebAssembly/binaryen"
+                .to_string(),
+        );
+        original.clone()
+    } else {
+        wasm::rewrite_core_modules(&original, &mut |module| {
+            let optimized = run_wasm_opt(module, &options.level)?;
+            check_exports(module, &optimized, "module")?;
+            Ok(optimized)
+        })?
+    };
+
+    if options.strip {
+        optimized = wasm::strip_custom_sections(&optimized, is_debug_section)?;
+    }
+
+    wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+        .validate_all(&optimized)
+        .context("Optimised artifact failed validation")?;
+
+    check_exports(&original, &optimized, "artifact")?;
+
+    fs::write(artifact, &optimized).context(format!("Failed to write {:?}", artifact))?;
+
+    Ok(OptimizeReport {
+        before: original.len(),
+        after: optimized.len(),
+        skipped_reason,
+        stripped: options.strip,
+    })
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read and parse the klave.json file at the root of the project
+pub fn read_config(cwd: &Path) -> Result<Value> {
+    let klave_config_path = cwd.join("klave.json");
+    if !klave_config_path.exists() {
+        return Err(anyhow!(
+            "Error: klave.json file not found. Make sure you are in a Klave project directory. Run 'klave create' to start a new project."
+        ));
+    }
+
+    let klave_config_str =
+        fs::read_to_string(&klave_config_path).context("Failed to read klave.json")?;
+
+    serde_json::from_str(&klave_config_str).context("Invalid JSON in klave.json")
+}
+
+/// Get the applications array of a klave.json configuration
+pub fn applications(config: &Value) -> Result<&Vec<Value>> {
+    config
+        .get("applications")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            anyhow!("Error: Invalid klave.json file structure. 'applications' array not found.")
+        })
+}
+
+/// Get the identifier of an application, preferring its slug over its name
+pub fn app_slug(application: &Value) -> &str {
+    application
+        .get("slug")
+        .and_then(|s| s.as_str())
+        .or_else(|| application.get("name").and_then(|s| s.as_str()))
+        .unwrap_or("unknown")
+}
+
+/// Find an application by slug or name
+pub fn find_app<'a>(applications: &'a [Value], name: &str) -> Option<&'a Value> {
+    applications.iter().find(|a| {
+        a.get("slug").and_then(|s| s.as_str()) == Some(name)
+            || a.get("name").and_then(|s| s.as_str()) == Some(name)
+    })
+}
+
+/// Select one application by name, or all of them when no name is given
+pub fn select_apps<'a>(applications: &'a [Value], name: Option<&str>) -> Result<Vec<&'a Value>> {
+    let selected = match name {
+        Some(name) => {
+            let app = find_app(applications, name).ok_or_else(|| {
+                // List available apps if the specified app wasn't found
+                let available_apps: Vec<&str> = applications.iter().map(app_slug).collect();
+                anyhow!(
+                    "Error: No application found with name \"{}\". Available applications: {}",
+                    name,
+                    available_apps.join(", ")
+                )
+            })?;
+            vec![app]
+        }
+        None => applications.iter().collect(),
+    };
+
+    if selected.is_empty() {
+        return Err(anyhow!("Error: No applications found in klave.json"));
+    }
+
+    Ok(selected)
+}
+
+/// Resolve the directory of an application from its rootDir
+pub fn app_dir(cwd: &Path, application: &Value) -> PathBuf {
+    let root_dir = application
+        .get("rootDir")
+        .and_then(|s| s.as_str())
+        .unwrap_or(".");
+
+    cwd.join(root_dir.strip_prefix('/').unwrap_or(root_dir))
+}
+
+/// Determine app type - simplified to just rust or assemblyscript
+pub fn app_type(app_dir: &Path) -> &'static str {
+    if app_dir.join("Cargo.toml").exists() {
+        "rust"
+    } else if app_dir.join("tsconfig.json").exists() {
+        "assemblyscript"
+    } else {
+        "unknown"
+    }
+}
+
+/// Read the optional `maxSize` budget of an application, in bytes
+///
+/// The budget is either a number of bytes or a string with a unit, e.g.
+/// `"256KiB"` or `"1.5MB"`.
+pub fn max_size(application: &Value) -> Result<Option<usize>> {
+    match application.get("maxSize") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_u64()
+            .map(|n| Some(n as usize))
+            .ok_or_else(|| anyhow!("Invalid maxSize {} for \"{}\"", n, app_slug(application))),
+        Some(Value::String(s)) => parse_size(s).map(Some).ok_or_else(|| {
+            anyhow!(
+                "Invalid maxSize \"{}\" for \"{}\". Use a number of bytes or a size such as \"256KiB\"",
+                s,
+                app_slug(application)
+            )
+        }),
+        Some(other) => Err(anyhow!(
+            "Invalid maxSize {} for \"{}\"",
+            other,
+            app_slug(application)
+        )),
+    }
+}
+
+/// Parse a size such as `512`, `100KB`, `256KiB` or `1.5MB` into bytes
+fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" => 1000.0,
+        "kib" => 1024.0,
+        "mb" => 1000.0 * 1000.0,
+        "mib" => 1024.0 * 1024.0,
+        _ => return None,
+    };
+
+    Some((number * multiplier) as usize)
+}
+
+/// Directory of the artifacts produced by `klave build` and of their manifest
+pub const DIST_DIR: &str = ".klave/dist";
+
+/// Locate the wasm artifact produced by `klave build` for an application
+///
+/// This is the compiler output after optimisation and provenance embedding,
+/// as deployed. The returned path may not exist if the application has not
+/// been built yet.
+pub fn artifact_path(cwd: &Path, application: &Value) -> Result<PathBuf> {
+    Ok(cwd
+        .join(DIST_DIR)
+        .join(format!("{}.wasm", app_slug(application))))
+}
+
+/// Locate the wasm file written by the compiler of an application
+///
+/// `klave build` copies it to `artifact_path` before post-processing, so the
+/// compiler output is never modified.
+pub fn compiler_output(cwd: &Path, application: &Value) -> Result<PathBuf> {
+    let app_dir = app_dir(cwd, application);
+
+    match app_type(&app_dir) {
+        "rust" => {
+            let crate_name = cargo_package_name(&app_dir)?.replace('-', "_");
+
+            Ok(cargo_target_dir(cwd, &app_dir)
+                .join("wasm32-unknown-unknown")
+                .join("release")
+                .join(format!("{}.wasm", crate_name)))
+        }
+        "assemblyscript" => Ok(cwd
+            .join(".klave")
+            .join(format!("{}.wasm", app_slug(application)))),
+        _ => Err(anyhow!(
+            "Could not determine app type for \"{}\"",
+            app_slug(application)
+        )),
+    }
+}
+
+/// Read the package name from the Cargo.toml of a Rust application
+pub fn cargo_package_name(app_dir: &Path) -> Result<String> {
+    let manifest_path = app_dir.join("Cargo.toml");
+    let manifest: toml::Value = fs::read_to_string(&manifest_path)
+        .context(format!("Failed to read {:?}", manifest_path))?
+        .parse()
+        .context(format!("Invalid TOML in {:?}", manifest_path))?;
+
+    manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(|n| n.to_string())
+        .ok_or_else(|| anyhow!("No package name found in {:?}", manifest_path))
+}
+
+/// Find the cargo target directory used when building a Rust application
+fn cargo_target_dir(cwd: &Path, app_dir: &Path) -> PathBuf {
+    if let Ok(target_dir) = env::var("CARGO_TARGET_DIR") {
+        return cwd.join(target_dir);
+    }
+
+    cargo_workspace_dir(cwd, app_dir).join("target")
+}
+
+/// Find the cargo workspace a Rust application belongs to
+///
+/// Walks up from the app to the project root looking for the workspace
+/// manifest, falling back to the app directory itself.
+pub fn cargo_workspace_dir(cwd: &Path, app_dir: &Path) -> PathBuf {
+    for dir in app_dir.ancestors() {
+        let is_workspace = fs::read_to_string(dir.join("Cargo.toml"))
+            .map(|content| content.contains("[workspace]"))
+            .unwrap_or(false);
+
+        if is_workspace {
+            return dir.to_path_buf();
+        }
+
+        if dir == cwd {
+            break;
+        }
+    }
+
+    app_dir.to_path_buf()
+}
+
+/// Merge `overlay` into `base`: objects are merged key by key, other values replaced
+pub fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use walkdir::WalkDir;
+
+use crate::util::{git, project, wasm};
+
+/// Name of the custom section holding build provenance
+pub const PROVENANCE_SECTION: &str = "klave-provenance";
+
+/// Directories that never contribute to the source hash
+const IGNORED_DIRS: [&str; 4] = ["target", "node_modules", ".git", ".klave"];
+
+/// Lock files and manifests at the project root that affect the build
+const PROJECT_FILES: [&str; 5] = [
+    "Cargo.lock",
+    "package.json",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+];
+
+/// Build provenance embedded in each artifact
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    pub slug: String,
+    pub version: String,
+    pub git_commit: Option<String>,
+    pub git_dirty: Option<bool>,
+    pub toolchain: BTreeMap<String, String>,
+    pub profile: String,
+    pub source_hash: String,
+    pub cli_version: String,
+}
+
+/// Get the first line printed by a tool's version command
+fn tool_version(command: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(command).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// Get the version of an installed npm package
+fn node_package_version(cwd: &Path, package: &str) -> Option<String> {
+    let manifest =
+        fs::read_to_string(cwd.join("node_modules").join(package).join("package.json")).ok()?;
+    serde_json::from_str::<Value>(&manifest)
+        .ok()?
+        .get("version")?
+        .as_str()
+        .map(|v| v.to_string())
+}
+
+/// Collect the versions of the tools used to build an app
+fn toolchain(cwd: &Path, app_type: &str) -> BTreeMap<String, String> {
+    let mut toolchain = BTreeMap::new();
+
+    let versions = match app_type {
+        "rust" => vec![
+            ("rustc", tool_version("rustc", &["--version"])),
+            (
+                "cargo-component",
+                tool_version("cargo", &["component", "--version"]),
+            ),
+        ],
+        "assemblyscript" => vec![
+            ("node", tool_version("node", &["--version"])),
+            (
+                "assemblyscript",
+                node_package_version(cwd, "assemblyscript"),
+            ),
+            ("@klave/sdk", node_package_version(cwd, "@klave/sdk")),
+        ],
+        _ => Vec::new(),
+    };
+
+    for (tool, version) in versions {
+        if let Some(version) = version {
+            toolchain.insert(tool.to_string(), version);
+        }
+    }
+
+    toolchain
+}
+
+/// Hash the sources of an application
+///
+/// Files are hashed in path order together with their path relative to the
+/// project, so renames change the hash. The project lock files are included
+/// since they pin the dependencies that end up in the artifact.
+pub fn source_hash(cwd: &Path, app_dir: &Path) -> Result<String> {
+    let mut files: Vec<_> = WalkDir::new(app_dir)
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && IGNORED_DIRS.contains(&e.file_name().to_string_lossy().as_ref()))
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect();
+
+    for file in PROJECT_FILES {
+        let path = cwd.join(file);
+        if path.is_file() && !files.contains(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file.strip_prefix(cwd).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(&file).context(format!("Failed to read {:?}", file))?);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Gather the provenance of an application build with the given profile
+pub fn collect(cwd: &Path, application: &Value, profile: String) -> Result<Provenance> {
+    let app_dir = project::app_dir(cwd, application);
+    let app_type = project::app_type(&app_dir);
+
+    Ok(Provenance {
+        slug: project::app_slug(application).to_string(),
+        version: application
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        git_commit: git::current_commit(cwd),
+        git_dirty: git::is_dirty(cwd),
+        toolchain: toolchain(cwd, app_type),
+        profile,
+        source_hash: source_hash(cwd, &app_dir)?,
+        cli_version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+/// Write provenance into an artifact, replacing any previous provenance section
+pub fn embed(artifact: &Path, provenance: &Provenance) -> Result<()> {
+    let bytes = fs::read(artifact).context(format!("Failed to read {:?}", artifact))?;
+    let bytes = wasm::strip_custom_sections(&bytes, |name| name == PROVENANCE_SECTION)?;
+    let bytes =
+        wasm::append_custom_section(bytes, PROVENANCE_SECTION, &serde_json::to_vec(provenance)?);
+    fs::write(artifact, bytes).context(format!("Failed to write {:?}", artifact))
+}
+
+/// Read the provenance embedded in a parsed artifact, if any
+pub fn read(info: &wasm::WasmInfo) -> Option<Result<Provenance>> {
+    info.custom_section(PROVENANCE_SECTION)
+        .map(|section| serde_json::from_slice(&section.data).context("Invalid provenance section"))
+}
@@ -0,0 +1,317 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::util::config;
+
+/// Secrets of a project
+#[derive(Serialize, Deserialize, Default)]
+pub struct Secrets {
+    /// Values by app slug then secret name
+    pub values: BTreeMap<String, BTreeMap<String, String>>,
+    /// Names removed locally by app slug, until a deploy removes them from
+    /// the platform
+    #[serde(default)]
+    pub removed: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Secrets {
+    /// Set a secret, returning whether it replaced a value
+    pub fn set(&mut self, app: &str, name: &str, value: String) -> bool {
+        if let Some(removed) = self.removed.get_mut(app) {
+            removed.remove(name);
+            if removed.is_empty() {
+                self.removed.remove(app);
+            }
+        }
+
+        self.values
+            .entry(app.to_string())
+            .or_default()
+            .insert(name.to_string(), value)
+            .is_some()
+    }
+
+    /// Remove a secret, returning whether it was set
+    ///
+    /// The name is kept so the next deploy removes it from the platform too.
+    pub fn unset(&mut self, app: &str, name: &str) -> bool {
+        let Some(values) = self.values.get_mut(app) else {
+            return false;
+        };
+        if values.remove(name).is_none() {
+            return false;
+        }
+        if values.is_empty() {
+            self.values.remove(app);
+        }
+
+        self.removed
+            .entry(app.to_string())
+            .or_default()
+            .insert(name.to_string());
+        true
+    }
+
+    /// Changes to push for an app, as a JSON merge patch: the values to set
+    /// and `None` for the secrets to remove
+    pub fn patch(&self, app: &str) -> BTreeMap<String, Option<String>> {
+        let mut patch: BTreeMap<String, Option<String>> = self
+            .removed
+            .get(app)
+            .into_iter()
+            .flatten()
+            .map(|name| (name.clone(), None))
+            .collect();
+        for (name, value) in self.values.get(app).into_iter().flatten() {
+            patch.insert(name.clone(), Some(value.clone()));
+        }
+        patch
+    }
+}
+
+/// Encrypted secrets file, kept in the project's `.klave` directory
+#[derive(Serialize, Deserialize)]
+struct SecretsFile {
+    version: u32,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn secrets_path(cwd: &Path) -> PathBuf {
+    cwd.join(".klave").join("secrets.json")
+}
+
+/// Key encrypting the secrets files of the current user
+///
+/// Read from `KLAVE_SECRETS_KEY` (base64, 32 bytes) when set, otherwise from
+/// `secrets.key` in the user config directory, generated on first use.
+fn secrets_key(create: bool) -> Result<Aes256Gcm> {
+    let key = if let Ok(key) = env::var("KLAVE_SECRETS_KEY") {
+        BASE64
+            .decode(key.trim())
+            .context("Invalid KLAVE_SECRETS_KEY, expected base64")?
+    } else {
+        let path = config::config_dir()?.join("secrets.key");
+        if path.exists() {
+            config::check_private_file(&path)?;
+            BASE64
+                .decode(fs::read_to_string(&path)?.trim())
+                .context(format!("Invalid secrets key in {:?}", path))?
+        } else if create {
+            let mut key = [0u8; 32];
+            OsRng.fill_bytes(&mut key);
+            config::write_private_file(&path, BASE64.encode(key).as_bytes())?;
+            key.to_vec()
+        } else {
+            return Err(anyhow!(
+                "No secrets key found at {:?}, the project secrets were set by another user or machine (set KLAVE_SECRETS_KEY)",
+                path
+            ));
+        }
+    };
+
+    let key: [u8; 32] = key
+        .try_into()
+        .map_err(|_| anyhow!("Invalid secrets key, expected 32 bytes"))?;
+    Ok(Aes256Gcm::new(&key.into()))
+}
+
+/// Check a secret name can be used as an environment variable
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(anyhow!(
+            "Invalid secret name \"{}\", use letters, digits and '_', not starting with a digit",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Whether secrets were ever set in a project
+pub fn exists(cwd: &Path) -> bool {
+    secrets_path(cwd).exists()
+}
+
+/// Read and decrypt the secrets of a project, empty when none are set
+pub fn load(cwd: &Path) -> Result<Secrets> {
+    let path = secrets_path(cwd);
+    if !path.exists() {
+        return Ok(Secrets::default());
+    }
+
+    read(&path, &secrets_key(false)?)
+}
+
+fn read(path: &Path, key: &Aes256Gcm) -> Result<Secrets> {
+    let file: SecretsFile = serde_json::from_str(
+        &fs::read_to_string(path).context(format!("Failed to read {:?}", path))?,
+    )
+    .context(format!("Invalid secrets file {:?}", path))?;
+
+    if file.cipher != "aes-256-gcm" {
+        return Err(anyhow!("Unsupported secrets encryption {}", file.cipher));
+    }
+
+    let plaintext = key
+        .decrypt(
+            Nonce::from_slice(&BASE64.decode(&file.nonce)?),
+            BASE64.decode(&file.ciphertext)?.as_slice(),
+        )
+        .map_err(|_| {
+            anyhow!(
+                "Could not decrypt {:?}, it was encrypted with another key",
+                path
+            )
+        })?;
+
+    match file.version {
+        // Version 1 holds the values only
+        1 => Ok(Secrets {
+            values: serde_json::from_slice(&plaintext).context("Invalid secrets file contents")?,
+            removed: BTreeMap::new(),
+        }),
+        2 => serde_json::from_slice(&plaintext).context("Invalid secrets file contents"),
+        version => Err(anyhow!(
+            "Unsupported secrets file version {}, update the Klave CLI",
+            version
+        )),
+    }
+}
+
+/// Encrypt and write the secrets of a project
+pub fn save(cwd: &Path, secrets: &Secrets) -> Result<()> {
+    write(&secrets_path(cwd), &secrets_key(true)?, secrets)
+}
+
+fn write(path: &Path, key: &Aes256Gcm, secrets: &Secrets) -> Result<()> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = key
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            serde_json::to_vec(secrets)?.as_slice(),
+        )
+        .map_err(|_| anyhow!("Failed to encrypt secrets"))?;
+
+    let file = SecretsFile {
+        version: 2,
+        cipher: "aes-256-gcm".to_string(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+
+    config::write_private_file(path, serde_json::to_string_pretty(&file)?.as_bytes())
+}
+
+/// Secrets of one application
+pub fn for_app(cwd: &Path, app: &str) -> Result<BTreeMap<String, String>> {
+    Ok(load(cwd)?.values.remove(app).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> Aes256Gcm {
+        Aes256Gcm::new(&[byte; 32].into())
+    }
+
+    #[test]
+    fn secrets_round_trip_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let mut secrets = Secrets::default();
+        secrets.set("hello", "API_KEY", "s3cr3t-value".to_string());
+        secrets.set("hello", "OLD_KEY", "old".to_string());
+        secrets.unset("hello", "OLD_KEY");
+
+        write(&path, &key(1), &secrets).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("s3cr3t-value"));
+        assert!(!contents.contains("API_KEY"));
+
+        let read = read(&path, &key(1)).unwrap();
+        assert_eq!(read.values, secrets.values);
+        assert_eq!(read.removed, secrets.removed);
+    }
+
+    #[test]
+    fn secrets_need_the_key_they_were_encrypted_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let mut secrets = Secrets::default();
+        secrets.set("hello", "API_KEY", "value".to_string());
+        write(&path, &key(1), &secrets).unwrap();
+
+        let error = read(&path, &key(2)).err().unwrap();
+        assert!(
+            error
+                .to_string()
+                .ends_with("it was encrypted with another key")
+        );
+    }
+
+    #[test]
+    fn version_1_files_hold_values_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let nonce = [3u8; 12];
+        let ciphertext = key(1)
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                br#"{"hello":{"API_KEY":"value"}}"#.as_slice(),
+            )
+            .unwrap();
+        let file = SecretsFile {
+            version: 1,
+            cipher: "aes-256-gcm".to_string(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+
+        let secrets = read(&path, &key(1)).unwrap();
+        assert_eq!(secrets.values["hello"]["API_KEY"], "value");
+        assert!(secrets.removed.is_empty());
+    }
+
+    #[test]
+    fn patch_removes_unset_secrets_until_set_again() {
+        let mut secrets = Secrets::default();
+        secrets.set("hello", "KEEP", "1".to_string());
+        secrets.set("hello", "DROP", "2".to_string());
+        secrets.set("other", "DROP", "3".to_string());
+
+        assert!(secrets.unset("hello", "DROP"));
+        assert!(!secrets.unset("hello", "MISSING"));
+        assert_eq!(
+            secrets.patch("hello"),
+            BTreeMap::from([
+                ("DROP".to_string(), None),
+                ("KEEP".to_string(), Some("1".to_string())),
+            ])
+        );
+        assert_eq!(secrets.patch("other").len(), 1);
+
+        assert!(!secrets.set("hello", "DROP", "4".to_string()));
+        assert_eq!(secrets.patch("hello")["DROP"], Some("4".to_string()));
+        assert!(secrets.removed.is_empty());
+    }
+}
@@ -0,0 +1,686 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use wasmparser::{
+    ComponentExternalKind, ComponentTypeRef, CompositeInnerType, Encoding, ExternalKind, FuncType,
+    KnownCustom, Name, Parser, Payload, TypeRef,
+};
+
+/// Whether a wasm binary is a core module or a component
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WasmKind {
+    Module,
+    Component,
+}
+
+impl std::fmt::Display for WasmKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasmKind::Module => write!(f, "core module"),
+            WasmKind::Component => write!(f, "component"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Clone)]
+pub struct Export {
+    pub name: String,
+    pub ty: String,
+}
+
+pub struct CustomSection {
+    pub name: String,
+    pub size: usize,
+    pub data: Vec<u8>,
+}
+
+pub struct FunctionSize {
+    pub index: u32,
+    pub name: Option<String>,
+    pub size: usize,
+}
+
+/// Summary of a core module, either standalone or embedded in a component
+#[derive(Default)]
+pub struct ModuleInfo {
+    pub imports: Vec<Import>,
+    pub exports: Vec<Export>,
+    pub data_segments: Vec<usize>,
+    pub functions: Vec<FunctionSize>,
+}
+
+impl ModuleInfo {
+    pub fn code_size(&self) -> usize {
+        self.functions.iter().map(|f| f.size).sum()
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.data_segments.iter().sum()
+    }
+}
+
+/// Everything `klave inspect` knows about a wasm binary
+pub struct WasmInfo {
+    pub kind: WasmKind,
+    pub size: usize,
+    /// Imports of the binary itself (component imports for components)
+    pub imports: Vec<Import>,
+    /// Exports of the binary itself (component exports for components)
+    pub exports: Vec<Export>,
+    /// Core modules, a single entry for a core module
+    pub modules: Vec<ModuleInfo>,
+    /// Custom sections at any nesting level
+    pub custom_sections: Vec<CustomSection>,
+    /// Producers metadata as (field, [(name, version)])
+    pub producers: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl WasmInfo {
+    pub fn custom_section(&self, name: &str) -> Option<&CustomSection> {
+        self.custom_sections.iter().find(|s| s.name == name)
+    }
+}
+
+/// State kept while walking a core module
+#[derive(Default)]
+struct ModuleBuilder {
+    info: ModuleInfo,
+    types: Vec<Option<FuncType>>,
+    func_types: Vec<u32>,
+    imported_funcs: u32,
+    names: HashMap<u32, String>,
+}
+
+impl ModuleBuilder {
+    fn signature(&self, type_index: u32) -> String {
+        match self.types.get(type_index as usize) {
+            Some(Some(ty)) => format_func_type(ty),
+            _ => format!("type {}", type_index),
+        }
+    }
+
+    fn finish(mut self) -> ModuleInfo {
+        for function in &mut self.info.functions {
+            function.name = self.names.get(&function.index).cloned();
+        }
+        self.info
+    }
+}
+
+enum Frame {
+    Module(Box<ModuleBuilder>),
+    Component,
+}
+
+/// Parse a wasm binary, either a core module or a component
+pub fn parse(bytes: &[u8]) -> Result<WasmInfo> {
+    let mut info = WasmInfo {
+        kind: WasmKind::Module,
+        size: bytes.len(),
+        imports: Vec::new(),
+        exports: Vec::new(),
+        modules: Vec::new(),
+        custom_sections: Vec::new(),
+        producers: Vec::new(),
+    };
+
+    let mut stack: Vec<Frame> = Vec::new();
+
+    for payload in Parser::new(0).parse_all(bytes) {
+        let payload = payload.context("Invalid wasm binary")?;
+        let top_level = stack.len() == 1;
+
+        match payload {
+            Payload::Version { encoding, .. } => match encoding {
+                Encoding::Module => stack.push(Frame::Module(Box::default())),
+                Encoding::Component => {
+                    if stack.is_empty() {
+                        info.kind = WasmKind::Component;
+                    }
+                    stack.push(Frame::Component);
+                }
+            },
+            Payload::End(_) => {
+                if let Some(Frame::Module(module)) = stack.pop() {
+                    let module = module.finish();
+                    if stack.is_empty() {
+                        info.imports = module.imports.clone();
+                        info.exports = module.exports.clone();
+                    }
+                    info.modules.push(module);
+                }
+            }
+            Payload::CustomSection(reader) => {
+                if let KnownCustom::Producers(producers) = reader.as_known() {
+                    for field in producers.into_iter().flatten() {
+                        let values = field
+                            .values
+                            .into_iter()
+                            .flatten()
+                            .map(|v| (v.name.to_string(), v.version.to_string()))
+                            .collect();
+                        info.producers.push((field.name.to_string(), values));
+                    }
+                } else if let KnownCustom::Name(names) = reader.as_known() {
+                    if let Some(Frame::Module(module)) = stack.last_mut() {
+                        for name in names.into_iter().flatten() {
+                            if let Name::Function(map) = name {
+                                for naming in map.into_iter().flatten() {
+                                    module.names.insert(naming.index, naming.name.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+
+                info.custom_sections.push(CustomSection {
+                    name: reader.name().to_string(),
+                    size: reader.data().len(),
+                    data: reader.data().to_vec(),
+                });
+            }
+            Payload::ComponentImportSection(reader) if top_level => {
+                for import in reader {
+                    let import = import?;
+                    info.imports.push(Import {
+                        module: String::new(),
+                        name: import.name.0.to_string(),
+                        ty: component_type_ref_kind(&import.ty).to_string(),
+                    });
+                }
+            }
+            Payload::ComponentExportSection(reader) if top_level => {
+                for export in reader {
+                    let export = export?;
+                    info.exports.push(Export {
+                        name: export.name.0.to_string(),
+                        ty: component_kind(export.kind).to_string(),
+                    });
+                }
+            }
+            payload => {
+                if let Some(Frame::Module(module)) = stack.last_mut() {
+                    parse_module_payload(module, payload)?;
+                }
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+fn parse_module_payload(module: &mut ModuleBuilder, payload: Payload) -> Result<()> {
+    match payload {
+        Payload::TypeSection(reader) => {
+            for rec_group in reader {
+                for sub_type in rec_group?.into_types() {
+                    module.types.push(match sub_type.composite_type.inner {
+                        CompositeInnerType::Func(ty) => Some(ty),
+                        _ => None,
+                    });
+                }
+            }
+        }
+        Payload::ImportSection(reader) => {
+            for import in reader {
+                let import = import?;
+                let ty = match import.ty {
+                    TypeRef::Func(index) => {
+                        module.func_types.push(index);
+                        module.imported_funcs += 1;
+                        format!("func {}", module.signature(index))
+                    }
+                    TypeRef::Memory(memory) => {
+                        format!("memory {}", format_limits(memory.initial, memory.maximum))
+                    }
+                    TypeRef::Table(table) => format!(
+                        "table {} {}",
+                        table.element_type,
+                        format_limits(table.initial, table.maximum)
+                    ),
+                    TypeRef::Global(global) => format!("global {}", global.content_type),
+                    TypeRef::Tag(_) => "tag".to_string(),
+                };
+                module.info.imports.push(Import {
+                    module: import.module.to_string(),
+                    name: import.name.to_string(),
+                    ty,
+                });
+            }
+        }
+        Payload::FunctionSection(reader) => {
+            for type_index in reader {
+                module.func_types.push(type_index?);
+            }
+        }
+        Payload::ExportSection(reader) => {
+            for export in reader {
+                let export = export?;
+                let ty = match export.kind {
+                    ExternalKind::Func => match module.func_types.get(export.index as usize) {
+                        Some(type_index) => format!("func {}", module.signature(*type_index)),
+                        None => "func".to_string(),
+                    },
+                    ExternalKind::Memory => "memory".to_string(),
+                    ExternalKind::Table => "table".to_string(),
+                    ExternalKind::Global => "global".to_string(),
+                    ExternalKind::Tag => "tag".to_string(),
+                };
+                module.info.exports.push(Export {
+                    name: export.name.to_string(),
+                    ty,
+                });
+            }
+        }
+        Payload::DataSection(reader) => {
+            for data in reader {
+                module.info.data_segments.push(data?.data.len());
+            }
+        }
+        Payload::CodeSectionEntry(body) => {
+            let index = module.imported_funcs + module.info.functions.len() as u32;
+            module.info.functions.push(FunctionSize {
+                index,
+                name: None,
+                size: body.range().len(),
+            });
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Format a core function type as `(i32, i32) -> i32`
+fn format_func_type(ty: &FuncType) -> String {
+    let params = ty
+        .params()
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match ty.results() {
+        [] => format!("({})", params),
+        [result] => format!("({}) -> {}", params, result),
+        results => format!(
+            "({}) -> ({})",
+            params,
+            results
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn component_type_ref_kind(ty: &ComponentTypeRef) -> &'static str {
+    match ty {
+        ComponentTypeRef::Module(_) => "module",
+        ComponentTypeRef::Func(_) => "func",
+        ComponentTypeRef::Value(_) => "value",
+        ComponentTypeRef::Type(_) => "type",
+        ComponentTypeRef::Instance(_) => "instance",
+        ComponentTypeRef::Component(_) => "component",
+    }
+}
+
+fn component_kind(kind: ComponentExternalKind) -> &'static str {
+    match kind {
+        ComponentExternalKind::Module => "module",
+        ComponentExternalKind::Func => "func",
+        ComponentExternalKind::Value => "value",
+        ComponentExternalKind::Type => "type",
+        ComponentExternalKind::Instance => "instance",
+        ComponentExternalKind::Component => "component",
+    }
+}
+
+fn format_limits(initial: u64, maximum: Option<u64>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", initial, maximum),
+        None => format!("{}..", initial),
+    }
+}
+
+/// Render the WIT world embedded in a component, if any
+pub fn wit_world(bytes: &[u8]) -> Option<String> {
+    let decoded = wit_component::decode(bytes).ok()?;
+    let mut printer = wit_component::WitPrinter::default();
+    printer
+        .print(decoded.resolve(), decoded.package(), &[])
+        .ok()?;
+    Some(printer.output.to_string())
+}
+
+/// Format a byte count for humans, e.g. `12.3 KiB`
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn read_leb_u32(bytes: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut result: u32 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or_else(|| anyhow!("Unexpected end of wasm binary"))?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+        if shift > 28 {
+            return Err(anyhow!("Invalid LEB128 in wasm binary"));
+        }
+    }
+}
+
+fn write_leb_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// A top-level section of a module or component, with its raw contents
+pub struct RawSection<'a> {
+    pub id: u8,
+    pub contents: &'a [u8],
+}
+
+impl RawSection<'_> {
+    /// Name of a custom section
+    fn custom_name(&self) -> Result<&str> {
+        let mut pos = 0;
+        let name_len = read_leb_u32(self.contents, &mut pos)? as usize;
+        Ok(self
+            .contents
+            .get(pos..pos + name_len)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .unwrap_or(""))
+    }
+}
+
+/// Split a module or component into its top-level sections
+pub fn raw_sections(bytes: &[u8]) -> Result<Vec<RawSection<'_>>> {
+    if bytes.len() < 8 || &bytes[0..4] != b"\0asm" {
+        return Err(anyhow!("Not a wasm binary"));
+    }
+
+    let mut sections = Vec::new();
+    let mut pos = 8;
+
+    while pos < bytes.len() {
+        let id = bytes[pos];
+        pos += 1;
+        let size = read_leb_u32(bytes, &mut pos)? as usize;
+        let contents = bytes
+            .get(pos..pos + size)
+            .ok_or_else(|| anyhow!("Section extends past the end of the wasm binary"))?;
+        pos += size;
+        sections.push(RawSection { id, contents });
+    }
+
+    Ok(sections)
+}
+
+/// Remove the custom sections whose name matches `should_strip`
+///
+/// Nested core modules and components are processed recursively, every other
+/// section is copied verbatim.
+pub fn strip_custom_sections(bytes: &[u8], should_strip: fn(&str) -> bool) -> Result<Vec<u8>> {
+    let sections = raw_sections(bytes)?;
+    let is_component = Parser::is_component(bytes);
+    let mut out = bytes[0..8].to_vec();
+
+    for section in sections {
+        let contents = match section.id {
+            0 if should_strip(section.custom_name()?) => continue,
+            // Nested core module and component sections
+            1 | 4 if is_component => strip_custom_sections(section.contents, should_strip)?,
+            _ => section.contents.to_vec(),
+        };
+        write_section(&mut out, section.id, &contents);
+    }
+
+    Ok(out)
+}
+
+/// Rewrite every core module of a binary
+///
+/// A core module is passed to `rewrite` as a whole. In a component each nested
+/// core module is, recursing into nested components, and every other section
+/// is copied verbatim.
+pub fn rewrite_core_modules(
+    bytes: &[u8],
+    rewrite: &mut dyn FnMut(&[u8]) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    if !Parser::is_component(bytes) {
+        return rewrite(bytes);
+    }
+
+    let mut out = bytes[0..8].to_vec();
+    for section in raw_sections(bytes)? {
+        let contents = match section.id {
+            1 => rewrite(section.contents)?,
+            4 => rewrite_core_modules(section.contents, rewrite)?,
+            _ => section.contents.to_vec(),
+        };
+        write_section(&mut out, section.id, &contents);
+    }
+
+    Ok(out)
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    write_leb_u32(out, contents.len() as u32);
+    out.extend_from_slice(contents);
+}
+
+/// Human readable labels for the top-level sections of a binary, e.g. `code`,
+/// `custom "name"` or `core module #1`
+pub fn section_labels(bytes: &[u8]) -> Result<Vec<(String, &[u8])>> {
+    const MODULE_SECTIONS: [&str; 14] = [
+        "custom",
+        "type",
+        "import",
+        "function",
+        "table",
+        "memory",
+        "global",
+        "export",
+        "start",
+        "element",
+        "code",
+        "data",
+        "data count",
+        "tag",
+    ];
+    const COMPONENT_SECTIONS: [&str; 12] = [
+        "custom",
+        "core module",
+        "core instance",
+        "core type",
+        "component",
+        "instance",
+        "alias",
+        "type",
+        "canonical",
+        "start",
+        "import",
+        "export",
+    ];
+
+    let names: &[&str] = if Parser::is_component(bytes) {
+        &COMPONENT_SECTIONS
+    } else {
+        &MODULE_SECTIONS
+    };
+
+    let mut counts: HashMap<u8, usize> = HashMap::new();
+    raw_sections(bytes)?
+        .into_iter()
+        .map(|section| {
+            let label = match (section.id, names.get(section.id as usize)) {
+                (0, _) => format!("custom \"{}\"", section.custom_name()?),
+                (_, Some(name)) => {
+                    let count = counts.entry(section.id).or_default();
+                    *count += 1;
+                    if *count > 1 {
+                        format!("{} #{}", name, count)
+                    } else {
+                        name.to_string()
+                    }
+                }
+                (id, None) => format!("unknown section {}", id),
+            };
+            Ok((label, section.contents))
+        })
+        .collect()
+}
+
+/// Append a custom section at the end of a module or component
+pub fn append_custom_section(mut bytes: Vec<u8>, name: &str, data: &[u8]) -> Vec<u8> {
+    let mut contents = Vec::new();
+    write_leb_u32(&mut contents, name.len() as u32);
+    contents.extend_from_slice(name.as_bytes());
+    contents.extend_from_slice(data);
+
+    bytes.push(0);
+    write_leb_u32(&mut bytes, contents.len() as u32);
+    bytes.extend_from_slice(&contents);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "register_routes"))
+        (data (i32.const 0) "hello")
+        (@custom "producers" "klave"))"#;
+
+    const COMPONENT: &str = r#"(component
+        (core module $m
+            (func (export "run"))
+            (@custom "name-like" "nested"))
+        (core instance $i (instantiate $m))
+        (func (export "run") (canon lift (core func $i "run"))))"#;
+
+    fn round_trip(value: u32) -> (usize, u32) {
+        let mut bytes = Vec::new();
+        write_leb_u32(&mut bytes, value);
+        let mut pos = 0;
+        let read = read_leb_u32(&bytes, &mut pos).unwrap();
+        assert_eq!(pos, bytes.len());
+        (bytes.len(), read)
+    }
+
+    #[test]
+    fn leb_boundaries() {
+        for (value, len) in [
+            (0, 1),
+            (127, 1),
+            (128, 2),
+            (16_383, 2),
+            (16_384, 3),
+            ((1 << 21) - 1, 3),
+            (1 << 21, 4),
+            ((1 << 28) - 1, 4),
+            (1 << 28, 5),
+            (u32::MAX, 5),
+        ] {
+            assert_eq!(round_trip(value), (len, value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn leb_rejects_truncated_and_overlong_values() {
+        assert!(read_leb_u32(&[0x80], &mut 0).is_err());
+        assert!(read_leb_u32(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01], &mut 0).is_err());
+    }
+
+    #[test]
+    fn raw_sections_rejects_broken_binaries() {
+        assert!(raw_sections(b"not wasm").is_err());
+        // A type section claiming 16 bytes with only one left
+        assert!(raw_sections(b"\0asm\x01\0\0\0\x01\x10\0").is_err());
+    }
+
+    fn strip_test_section(name: &str) -> bool {
+        name == "klave-test"
+    }
+
+    #[test]
+    fn appended_section_strips_back_to_the_module() {
+        for source in [MODULE, COMPONENT] {
+            let original = wat::parse_str(source).unwrap();
+            let appended = append_custom_section(original.clone(), "klave-test", b"{}");
+            let info = parse(&appended).unwrap();
+            assert_eq!(info.custom_section("klave-test").unwrap().data, b"{}");
+
+            let stripped = strip_custom_sections(&appended, strip_test_section).unwrap();
+            assert_eq!(stripped, original);
+        }
+    }
+
+    #[test]
+    fn rewrite_reaches_every_core_module() {
+        let module = wat::parse_str(MODULE).unwrap();
+        let mut seen = 0;
+        let rewritten = rewrite_core_modules(&module, &mut |bytes| {
+            seen += 1;
+            Ok(bytes.to_vec())
+        })
+        .unwrap();
+        assert_eq!((seen, rewritten), (1, module));
+
+        let component = wat::parse_str(COMPONENT).unwrap();
+        let rewritten = rewrite_core_modules(&component, &mut |bytes| {
+            strip_custom_sections(bytes, |name| name == "name-like")
+        })
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            strip_custom_sections(&component, |name| name == "name-like").unwrap()
+        );
+    }
+
+    #[test]
+    fn strip_recurses_into_nested_modules() {
+        let component = wat::parse_str(COMPONENT).unwrap();
+        let stripped = strip_custom_sections(&component, |name| name == "name-like").unwrap();
+        assert!(stripped.len() < component.len());
+        wasmparser::Validator::new()
+            .validate_all(&stripped)
+            .unwrap();
+        assert!(
+            !stripped
+                .windows(b"name-like".len())
+                .any(|window| window == b"name-like")
+        );
+    }
+}